@@ -0,0 +1,195 @@
+use super::parser::{self, ParseError};
+use super::network::Network;
+
+// Encodes a version byte and payload as base58 with a 4 byte double sha256 checksum
+pub fn encode_base58check(version: u8, payload: &[u8]) -> String {
+    let mut bytes = [&[version][..], payload].concat();
+    let checksum = parser::hash256(&bytes);
+    bytes.extend_from_slice(&checksum[0..4]);
+    return bs58::encode(bytes).into_string()
+}
+
+// Decodes a base58check string into its version byte and payload, verifying the checksum
+pub fn decode_base58check(address: &str) -> Result<(u8, Vec<u8>), ParseError> {
+    let bytes = bs58::decode(address).into_vec().map_err(|e| match e {
+        bs58::decode::Error::InvalidCharacter { character, index } => ParseError::new(index, "base58 address", &format!("invalid character '{}'", character)),
+        _ => ParseError::new(0, "base58 address", &e.to_string())
+    })?;
+    if bytes.len() < 5 { return Err(ParseError::new(0, "base58 address", "too short for a version byte and checksum")) }
+    let (data, checksum) = bytes.split_at(bytes.len()-4);
+    if parser::hash256(data)[0..4] != *checksum { return Err(ParseError::new(0, "base58 address", "invalid checksum")) }
+    return Ok((data[0], data[1..].to_vec()))
+}
+
+// BIP173 bech32 is used for segwit v0, BIP350 bech32m for v1 (taproot) and up
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bech32Variant {
+    Bech32, Bech32m
+}
+
+impl Bech32Variant {
+    fn checksum_constant(&self) -> u32 {
+        return match self {
+            Bech32Variant::Bech32 => 1,
+            Bech32Variant::Bech32m => 0x2bc830a3
+        }
+    }
+}
+
+const BECH32_CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+fn bech32_polymod(values: &[u8]) -> u32 {
+    const GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+    let mut checksum: u32 = 1;
+    for value in values {
+        let top = checksum >> 25;
+        checksum = (checksum & 0x1ffffff) << 5 ^ *value as u32;
+        for i in 0..5 {
+            if (top >> i) & 1 == 1 { checksum ^= GENERATOR[i] }
+        }
+    }
+    return checksum
+}
+
+// The hrp is checksummed as its high bits, a zero, then its low bits
+fn bech32_hrp_expand(hrp: &str) -> Vec<u8> {
+    let mut expanded: Vec<u8> = hrp.bytes().map(|c| c >> 5).collect();
+    expanded.push(0);
+    expanded.extend(hrp.bytes().map(|c| c & 31));
+    return expanded
+}
+
+// Encodes an hrp and 5 bit values (lowercase)
+pub fn encode_bech32(hrp: &str, data: &[u8], variant: Bech32Variant) -> String {
+    let values = [bech32_hrp_expand(hrp), data.to_vec(), vec![0; 6]].concat();
+    let polymod = bech32_polymod(&values) ^ variant.checksum_constant();
+    let checksum = (0..6).map(|i| ((polymod >> (5 * (5 - i))) & 31) as u8);
+    let characters: String = data.iter().copied().chain(checksum).map(|value| BECH32_CHARSET[value as usize] as char).collect();
+    return format!("{}1{}", hrp, characters)
+}
+
+// Decodes a bech32 or bech32m string into its (lowercase) hrp and 5 bit values, without the checksum
+pub fn decode_bech32(address: &str) -> Result<(String, Vec<u8>, Bech32Variant), ParseError> {
+    const FIELD: &str = "bech32 address";
+    if address.len() > 90 { return Err(ParseError::new(90, FIELD, "longer than 90 characters")) }
+    if address.bytes().any(|c| c.is_ascii_lowercase()) && address.bytes().any(|c| c.is_ascii_uppercase()) {
+        return Err(ParseError::new(0, FIELD, "mixed case"))
+    }
+    if let Some(index) = address.bytes().position(|c| !(33..=126).contains(&c)) {
+        return Err(ParseError::new(index, FIELD, "invalid character"))
+    }
+    let address = address.to_lowercase();
+    let separator = match address.rfind('1') {
+        Some(separator) if separator > 0 => separator,
+        _ => return Err(ParseError::new(0, FIELD, "missing hrp or separator"))
+    };
+    if address.len() - separator - 1 < 6 { return Err(ParseError::new(separator, FIELD, "too short for a checksum")) }
+    let hrp = &address[..separator];
+    let mut data = vec![];
+    for (index, c) in address.bytes().enumerate().skip(separator + 1) {
+        match BECH32_CHARSET.iter().position(|x| *x == c) {
+            Some(value) => data.push(value as u8),
+            None => return Err(ParseError::new(index, FIELD, &format!("invalid character '{}'", c as char)))
+        }
+    }
+    let variant = match bech32_polymod(&[bech32_hrp_expand(hrp), data.clone()].concat()) {
+        1 => Bech32Variant::Bech32,
+        0x2bc830a3 => Bech32Variant::Bech32m,
+        _ => return Err(ParseError::new(0, FIELD, "invalid checksum"))
+    };
+    data.truncate(data.len() - 6);
+    return Ok((hrp.to_string(), data, variant))
+}
+
+// Regroups bits, e.g. bytes into 5 bit values (padding with zeros) or back (rejecting leftover bits)
+fn convert_bits(data: &[u8], from: u32, to: u32, pad: bool) -> Option<Vec<u8>> {
+    let (mut accumulator, mut bits, mut converted) = (0_u32, 0, vec![]);
+    let max_value = (1 << to) - 1;
+    for value in data {
+        if (*value as u32) >> from != 0 { return None }
+        accumulator = (accumulator << from) | *value as u32;
+        bits += from;
+        while bits >= to {
+            bits -= to;
+            converted.push(((accumulator >> bits) & max_value) as u8);
+        }
+    }
+    if pad {
+        if bits > 0 { converted.push(((accumulator << (to - bits)) & max_value) as u8) }
+    }
+    else if bits >= from || ((accumulator << (to - bits)) & max_value) != 0 {
+        return None
+    }
+    return Some(converted)
+}
+
+// Encodes a witness program as a segwit address (v0 uses bech32, later versions bech32m)
+pub fn encode_segwit_address(hrp: &str, version: u8, program: &[u8]) -> String {
+    let variant = if version == 0 { Bech32Variant::Bech32 } else { Bech32Variant::Bech32m };
+    let data = [vec![version], convert_bits(program, 8, 5, true).unwrap()].concat();
+    return encode_bech32(hrp, &data, variant)
+}
+
+// Decodes a segwit address into its hrp, witness version and witness program
+pub fn decode_segwit_address(address: &str) -> Result<(String, u8, Vec<u8>), ParseError> {
+    const FIELD: &str = "segwit address";
+    let (hrp, data, variant) = decode_bech32(address)?;
+    if data.is_empty() { return Err(ParseError::new(0, FIELD, "missing witness version")) }
+    let version = data[0];
+    if version > 16 { return Err(ParseError::new(0, FIELD, &format!("invalid witness version {}", version))) }
+    let expected_variant = if version == 0 { Bech32Variant::Bech32 } else { Bech32Variant::Bech32m };
+    if variant != expected_variant { return Err(ParseError::new(0, FIELD, &format!("witness version {} can't use {:?}", version, variant))) }
+    let program = convert_bits(&data[1..], 5, 8, false).ok_or_else(|| ParseError::new(0, FIELD, "invalid padding"))?;
+    if program.len() < 2 || program.len() > 40 { return Err(ParseError::new(0, FIELD, &format!("invalid program length {}", program.len()))) }
+    if version == 0 && program.len() != 20 && program.len() != 32 { return Err(ParseError::new(0, FIELD, "v0 programs are 20 or 32 bytes")) }
+    return Ok((hrp, version, program))
+}
+
+// Turns an address into the scriptpubkey it pays to, failing if it's meant for another network
+// Testnet, signet and regtest share base58 prefixes, so those can't be told apart
+pub fn address_to_scriptpubkey(address: &str, network: &Network) -> Result<Vec<u8>, ParseError> {
+    let segwit = decode_segwit_address(address);
+    if address.to_lowercase().starts_with(&format!("{}1", network.bech32_hrp())) || segwit.is_ok() {
+        let (hrp, version, program) = segwit?;
+        if hrp != network.bech32_hrp() { return Err(ParseError::new(0, "address", &format!("hrp {} is not for {}", hrp, network.name()))) }
+        let version_opcode = if version == 0 { 0x00 } else { 0x50 + version }; // OP_0 or OP_1 to OP_16
+        return Ok([vec![version_opcode, program.len() as u8], program].concat())
+    }
+    let (version, payload) = decode_base58check(address)?;
+    if payload.len() != 20 { return Err(ParseError::new(0, "address", "base58 payload is not a 20 byte hash")) }
+    if version == network.p2pkh_prefix() {
+        return Ok([&[0x76, 0xa9, 0x14][..], &payload, &[0x88, 0xac]].concat()) // OP_DUP OP_HASH160 <Hash160> OP_EQUALVERIFY OP_CHECKSIG
+    }
+    if version == network.p2sh_prefix() {
+        return Ok([&[0xa9, 0x14][..], &payload, &[0x87]].concat()) // OP_HASH160 <Hash160> OP_EQUAL
+    }
+    return Err(ParseError::new(0, "address", &format!("version byte {} is not for {}", version, network.name())))
+}
+
+// Guesses the network of an address, testnet stands in for signet as they share prefixes and hrp
+pub fn address_network(address: &str) -> Result<Network, ParseError> {
+    let networks = [Network::Mainnet, Network::Testnet, Network::Regtest]; // regtest after testnet, so base58 matches testnet
+    if let Ok((hrp, _, _)) = decode_segwit_address(address) {
+        return networks.into_iter().find(|n| n.bech32_hrp() == hrp).ok_or_else(|| ParseError::new(0, "address", &format!("unknown hrp {}", hrp)))
+    }
+    let (version, _) = decode_base58check(address)?;
+    return networks.into_iter().find(|n| n.p2pkh_prefix() == version || n.p2sh_prefix() == version).ok_or_else(|| ParseError::new(0, "address", &format!("unknown version byte {}", version)))
+}
+
+// Turns a scriptpubkey into its address (None for scripts without one, e.g. op_return)
+pub fn scriptpubkey_to_address(scriptpubkey: &[u8], network: &Network) -> Option<String> {
+    let s = scriptpubkey;
+    if s.len() == 25 && s[..3] == [0x76, 0xa9, 0x14] && s[23..] == [0x88, 0xac] {
+        return Some(encode_base58check(network.p2pkh_prefix(), &s[3..23]))
+    }
+    if s.len() == 23 && s[..2] == [0xa9, 0x14] && s[22] == 0x87 {
+        return Some(encode_base58check(network.p2sh_prefix(), &s[2..22]))
+    }
+    let is_version_opcode = s.first().is_some_and(|op| *op == 0x00 || (0x51..=0x60).contains(op));
+    if is_version_opcode && s.len() >= 4 && s.len() <= 42 && s[1] as usize == s.len() - 2 {
+        let version = if s[0] == 0x00 { 0 } else { s[0] - 0x50 };
+        if version == 0 && s.len() != 22 && s.len() != 34 { return None }
+        return Some(encode_segwit_address(network.bech32_hrp(), version, &s[2..]))
+    }
+    return None
+}
@@ -0,0 +1,118 @@
+use std::fmt;
+use super::transaction::{self, Txid, Amount, Sequence};
+use super::chain_spec::ChainSpec;
+use super::parser;
+use super::ecc;
+use super::interpreter;
+use super::covenant::{CovenantType, Covenant};
+
+// The first thing wrong with a covenant chain: which tx, which check and what was expected instead
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    pub tx_index: usize, pub txid: Option<Txid>, pub check: &'static str, pub expected: String, pub found: String
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let txid = self.txid.map(|txid| format!(" ({})", txid)).unwrap_or_default();
+        return write!(f, "covenant tx {}{} fails the {} check\n  expected: {}\n  found:    {}", self.tx_index, txid, self.check, self.expected, self.found)
+    }
+}
+
+impl std::error::Error for Violation {}
+
+// Summary of a chain that passed every check
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditReport {
+    pub txs: usize, pub first_txid: Option<Txid>, pub last_txid: Option<Txid>, pub remaining: Amount
+}
+
+impl fmt::Display for AuditReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match (self.first_txid, self.last_txid) {
+            (Some(first), Some(last)) => write!(f, "{} covenant txs pass every check, from {} to {} (the last covenant output holds {} sats)", self.txs, first, last, self.remaining),
+            _ => write!(f, "the covenant chain is empty")
+        }
+    }
+}
+
+// Walks a presigned chain (raw txs as produced by generate_covenant_tx_sequence) and stops at the first violation
+// Checks the spent outpoint, sequence, amounts, both output scripts, the signature against the published pubkey
+// and finally runs the scripts, so an auditor doesn't have to trust whoever generated the chain (only its spec)
+pub fn audit_covenant_tx_sequence(spec: &ChainSpec, covenant_tx_sequence: &[String]) -> Result<AuditReport, Violation> {
+    let key = ecc::ECC::from_pk_string(&spec.pubkey).map_err(|e| Violation {
+        tx_index: 0, txid: None, check: "public key", expected: "a valid public key".to_string(), found: e.to_string()
+    })?;
+    let covenant = Covenant::new(&key, spec.covenant_type, spec.csv_delay, spec.dust_limit);
+    let (covenant_script, covenant_output, bump_output, vsize) = (covenant.script, covenant.output, covenant.bump_output, covenant.vsize);
+
+    let (mut spent_txid, mut spent_vout, mut input_satoshis) = (spec.genesis_txid, spec.genesis_vout, spec.input_satoshis);
+    let mut report = AuditReport { txs: 0, first_txid: None, last_txid: None, remaining: input_satoshis };
+    for (tx_index, tx_string) in covenant_tx_sequence.iter().enumerate() {
+        let violation = |txid: Option<Txid>, check: &'static str, expected: String, found: String| Violation { tx_index, txid, check, expected, found };
+        let tx = parser::decode_from_hex(tx_string).map_err(|e| violation(None, "decoding", "a raw transaction".to_string(), e.to_string()))?;
+        let txid = Some(tx.txid);
+        let check = |check: &'static str, ok: bool, expected: String, found: String| if ok { Ok(()) } else { Err(violation(txid, check, expected, found)) };
+
+        check("shape", tx.inputs.len() == 1 && tx.outputs.len() == 2, "1 input and 2 outputs".to_string(), format!("{} inputs and {} outputs", tx.inputs.len(), tx.outputs.len()))?;
+        let input = &tx.inputs[0];
+        check("spent outpoint", input.txid == spent_txid && input.vout == spent_vout, format!("{}:{}", spent_txid, spent_vout), format!("{}:{}", input.txid, input.vout))?;
+        check("sequence", input.sequence == Sequence(spec.csv_delay as u32), format!("{} (the covenant's relative locktime)", spec.csv_delay), input.sequence.0.to_string())?;
+        let expected_value = spec.step_cost(tx_index as u64, vsize).and_then(|cost| Some((input_satoshis.checked_sub(cost)?, cost)));
+        check("covenant value", Some(tx.outputs[0].value) == expected_value.map(|(value, _)| value),
+            expected_value.map(|(value, cost)| format!("{} sats ({} minus the step's cost of {})", value, input_satoshis, cost)).unwrap_or("no more than the remaining sats".to_string()),
+            format!("{} sats", tx.outputs[0].value))?;
+        check("covenant output", tx.outputs[0].scriptpubkey == covenant_output, covenant_output.to_asm(), tx.outputs[0].scriptpubkey.to_asm())?;
+        check("bump output", tx.outputs[1].scriptpubkey == bump_output, bump_output.to_asm(), tx.outputs[1].scriptpubkey.to_asm())?;
+        check("bump value", tx.outputs[1].value == spec.dust_limit, format!("{} sats (the dust limit)", spec.dust_limit), format!("{} sats", tx.outputs[1].value))?;
+
+        let prevout = transaction::Output { value: input_satoshis, scriptpubkey: covenant_output.clone() };
+        let signature = verify_signature(&tx, &key, &covenant_script, &prevout, spec.covenant_type);
+        check("signature", signature.is_ok(), format!("a valid signature by {}", spec.pubkey), signature.err().unwrap_or_default())?;
+        let scripts = interpreter::verify_input(&tx, 0, &[Some(prevout)]);
+        check("script", scripts.is_ok(), "the input scripts to pass".to_string(), scripts.err().map(|e| e.to_string()).unwrap_or_default())?;
+
+        report.txs += 1;
+        report.first_txid = report.first_txid.or(txid);
+        report.last_txid = txid;
+        report.remaining = tx.outputs[0].value;
+        (spent_txid, spent_vout, input_satoshis) = (tx.txid, 0, tx.outputs[0].value);
+    }
+    return Ok(report)
+}
+
+// Finds the covenant signature (scriptsig for p2sh, witness otherwise) and checks it with only the public key
+fn verify_signature(tx: &transaction::Content, key: &ecc::ECC, covenant_script: &parser::Script, prevout: &transaction::Output, covenant_type: CovenantType) -> Result<(), String> {
+    let sig = match covenant_type {
+        CovenantType::P2sh => match tx.inputs[0].scriptsig.instructions().map_err(|e| e.to_string())?.as_slice() {
+            [parser::Instruction::Push(sig), parser::Instruction::Push(script)] if script == covenant_script.as_bytes() => sig.clone(),
+            _ => return Err("the scriptsig isn't a signature and the covenant script".to_string())
+        },
+        _ => match tx.inputs[0].witness.as_slice() {
+            [sig, script] if covenant_type == CovenantType::P2wsh && script == covenant_script.as_bytes() => sig.clone(),
+            [sig, script, _] if covenant_type == CovenantType::P2tr && script == covenant_script.as_bytes() => sig.clone(),
+            _ => return Err("the witness isn't a signature and the covenant script".to_string())
+        }
+    };
+    let valid = match covenant_type {
+        CovenantType::P2tr => { // SIGHASH_DEFAULT signatures are 64 bytes and commit to every output
+            let sig: [u8; 64] = sig.try_into().map_err(|_| "not a 64 byte SIGHASH_DEFAULT schnorr signature".to_string())?;
+            let leaf_hash = parser::get_tapleaf_hash(covenant_script.as_bytes());
+            let sighash = parser::get_taproot_sighash(tx, 0, std::slice::from_ref(prevout), Some(&leaf_hash), parser::SIGHASH_DEFAULT)
+                .ok_or_else(|| "no taproot sighash for the covenant input".to_string())?;
+            key.verify_schnorr_hash(&sig, &sighash)
+        }
+        _ => {
+            let sig = ecc::Signature::from_bytes(&sig).map_err(|e| e.to_string())?;
+            // anything but SIGHASH_ALL would let whoever broadcasts a tx change its outputs
+            if sig.sighash_type as u32 != parser::SIGHASH_ALL { return Err(format!("a signature with sighash type 0x{:02x} instead of SIGHASH_ALL", sig.sighash_type)) }
+            let sighash = match covenant_type {
+                CovenantType::P2sh => parser::get_legacy_sighash(tx, 0, covenant_script.as_bytes(), sig.sighash_type as u32),
+                _ => parser::get_segwit_v0_sighash(tx, 0, covenant_script.as_bytes(), prevout.value.to_sat(), sig.sighash_type as u32)
+            };
+            key.verify_ecdsa_hash(&sig, &sighash)
+        }
+    };
+    if !valid { return Err("a signature that doesn't match the covenant key".to_string()) }
+    return Ok(())
+}
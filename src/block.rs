@@ -0,0 +1,12 @@
+use super::transaction;
+
+// Hashes are stored in serialization order (the reverse of how Core displays them)
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockHeader {
+    pub version: u64, pub prev_blockhash: [u8; 32], pub merkle_root: [u8; 32], pub time: u64, pub bits: u64, pub nonce: u64
+}
+
+#[derive(Debug)]
+pub struct Block {
+    pub header: BlockHeader, pub transactions: Vec<transaction::Content>
+}
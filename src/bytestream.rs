@@ -1,55 +1,136 @@
-pub struct Bytestream {
-    bytes: String, index: usize
+use std::fmt;
+use super::compact_size;
+
+// Describes where and why decoding failed (offset is in bytes from the start of the stream)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub offset: usize, pub field: String, pub reason: String
+}
+
+impl ParseError {
+    pub fn new(offset: usize, field: &str, reason: &str) -> ParseError {
+        return ParseError { offset, field: field.to_string(), reason: reason.to_string() }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "parse error at byte {} while reading {}: {}", self.offset, self.field, self.reason)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+// Reads fields from a byte slice without copying it
+pub struct Bytestream<'a> {
+    bytes: &'a [u8], index: usize
 }
 
-impl Bytestream {
-    pub fn new(bytes: &str) -> Bytestream {
-        return Bytestream { bytes: bytes.to_string(), index: 0 }
-    }
-
-    // Consumes x bytes and returns them as a u8 vector
-    pub fn get_bytes(&mut self, bytes: u64, convert_endian: bool) -> Vec<u8> {
-        let (start, end) = (self.index, self.index+bytes as usize*2);
-        self.index += bytes as usize*2;
-        let mut string = self.bytes[start..end].to_string();
-        if convert_endian { string = Bytestream::convert_endian(&string) }
-        let byte_vector = hex::decode(string).unwrap();
-        return byte_vector
-    }
-
-    // Consumes a variable number of bytes (max 1+8) and returns a u64
-    pub fn get_varint(&mut self) -> u64 {
-        let integer = self.get_bytes(1, false)[0]; // endian skippable if 1 byte
-        let i = u8::MAX - integer;
-        if i>2 { return integer as u64 }
-        let bytes = if i==0 { 8 } else if i==1 { 4 } else { 2 };
-        let byte_vector = self.get_bytes(bytes, true);
-        return Bytestream::bytes_to_u64(&byte_vector)
-    }
-
-    // Converts big <-> little endian
-    pub fn convert_endian(string: &str) -> String {
-        let mut new_string = String::new();
-        let mut prev_char = ' ';
-        for (i, curr_char) in string.chars().rev().enumerate() {
-            if i%2 == 0 { 
-                prev_char = curr_char;
-                continue
-            }
-            new_string.push(curr_char);
-            new_string.push(prev_char);
+impl<'a> Bytestream<'a> {
+    pub fn new(bytes: &'a [u8]) -> Bytestream<'a> {
+        return Bytestream { bytes, index: 0 }
+    }
+
+    // Current position in bytes
+    pub fn offset(&self) -> usize {
+        return self.index
+    }
+
+    // Number of bytes that haven't been consumed yet
+    pub fn remaining(&self) -> usize {
+        return self.bytes.len() - self.index
+    }
+
+    // Consumes x bytes and returns them as a slice
+    pub fn get_bytes(&mut self, bytes: u64, field: &str) -> Result<&'a [u8], ParseError> {
+        let remaining = self.remaining() as u64;
+        if bytes > remaining {
+            return Err(ParseError::new(self.index, field, &format!("needs {} bytes but only {} remain", bytes, remaining)))
         }
-        return new_string
+        let start = self.index;
+        self.index += bytes as usize;
+        return Ok(&self.bytes[start..self.index])
+    }
+
+    // Consumes 32 bytes (e.g. a txid or hash, kept in serialization order)
+    pub fn get_hash(&mut self, field: &str) -> Result<[u8; 32], ParseError> {
+        let mut hash = [0; 32];
+        hash.copy_from_slice(self.get_bytes(32, field)?);
+        return Ok(hash)
     }
 
-    // Converts a u8 vector (max length = 8) to a single u64 value
-    pub fn bytes_to_u64(byte_vector: &Vec<u8>) -> u64 {
-        if byte_vector.len()>8 { panic!("Input exceeds u64 size") }
-        let mut varint = 0;
-        for byte in byte_vector {
-            varint <<= 8;
-            varint |= *byte as u64;
+    // Consumes x little endian bytes (max 8) and returns them as a u64
+    pub fn get_int(&mut self, bytes: u64, field: &str) -> Result<u64, ParseError> {
+        if bytes > 8 { return Err(ParseError::new(self.index, field, "integer exceeds u64 size")) }
+        let byte_slice = self.get_bytes(bytes, field)?;
+        let mut int = 0;
+        for byte in byte_slice.iter().rev() {
+            int <<= 8;
+            int |= *byte as u64;
         }
-        return varint
+        return Ok(int)
     }
-}
\ No newline at end of file
+
+    // Consumes a compact size (1, 3, 5 or 9 bytes), see compact_size::decode for what gets rejected
+    pub fn get_varint(&mut self, field: &str) -> Result<u64, ParseError> {
+        return compact_size::decode(self, field)
+    }
+
+    // Consumes a varint length followed by that many bytes
+    pub fn get_length_prefixed(&mut self, field: &str) -> Result<&'a [u8], ParseError> {
+        let size = self.get_varint(field)?;
+        return self.get_bytes(size, field)
+    }
+
+    // Fails if anything is left after the last expected field
+    pub fn expect_end(&self, field: &str) -> Result<(), ParseError> {
+        if self.remaining() > 0 {
+            return Err(ParseError::new(self.index, field, &format!("{} trailing bytes", self.remaining())))
+        }
+        return Ok(())
+    }
+}
+
+// Appends fields to a growing byte vector (the counterpart of Bytestream)
+#[derive(Default)]
+pub struct ByteWriter {
+    bytes: Vec<u8>
+}
+
+impl ByteWriter {
+    pub fn new() -> ByteWriter {
+        return ByteWriter { bytes: vec![] }
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    // Writes the lowest x bytes of an int in little endian
+    pub fn write_int(&mut self, value: u64, bytes: usize) {
+        self.bytes.extend_from_slice(&value.to_le_bytes()[..bytes]);
+    }
+
+    // Writes an int as a compact size (marker byte first for values of 0xfd and up)
+    pub fn write_varint(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&compact_size::encode(value));
+    }
+
+    // Writes bytes prefixed with their length as a varint
+    pub fn write_length_prefixed(&mut self, bytes: &[u8]) {
+        self.write_varint(bytes.len() as u64);
+        self.write_bytes(bytes);
+    }
+
+    pub fn len(&self) -> usize {
+        return self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.bytes.is_empty()
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        return self.bytes
+    }
+}
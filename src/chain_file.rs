@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use super::transaction::{self, Txid};
+use super::parser::{self, ParseError};
+use super::chain_index::ChainIndex;
+use super::chain_spec::{ChainSpec, RawChainSpec};
+use super::covenant::{self, Covenant};
+use super::ecc;
+
+// Bumped whenever a field is added or changes meaning, older binaries refuse newer files
+pub const CHAIN_FILE_VERSION: u32 = 1;
+
+// A presigned covenant chain as it gets published: its spec and every tx, but not the key that signed them
+#[derive(Debug, Clone)]
+pub struct ChainFile {
+    pub spec: ChainSpec, pub txs: Vec<transaction::Content>,
+    steps: HashMap<Txid, usize> // which step each txid is, built once when the chain is loaded or generated
+}
+
+impl ChainFile {
+    pub fn new(spec: ChainSpec, txs: Vec<transaction::Content>) -> ChainFile {
+        let steps = txs.iter().enumerate().map(|(step, tx)| (tx.txid, step)).collect();
+        return ChainFile { spec, txs, steps }
+    }
+
+    // Which step a txid is (None for the genesis txid, which comes before step 0)
+    pub fn step(&self, txid: &Txid) -> Option<usize> {
+        return self.steps.get(txid).copied()
+    }
+
+    pub fn tx_strings(&self) -> Vec<String> {
+        return self.txs.iter().map(parser::encode_to_hex).collect()
+    }
+
+    pub fn index(&self) -> ChainIndex<'_> {
+        return ChainIndex::new(self)
+    }
+
+    pub fn to_json(&self) -> String {
+        let raw = RawChainFile {
+            version:  CHAIN_FILE_VERSION,
+            spec:     self.spec.to_raw(),
+            txs:      self.txs.iter().map(|tx| RawChainTx { txid: tx.txid.to_hex(), hex: parser::encode_to_hex(tx) }).collect()
+        };
+        return serde_json::to_string_pretty(&raw).unwrap()
+    }
+
+    // Reads a chain file, every tx has to match the txid listed next to it
+    pub fn from_json(json: &str) -> Result<ChainFile, ParseError> {
+        let raw: RawChainFile = serde_json::from_str(json).map_err(|e| ParseError::new(0, "json", &e.to_string()))?;
+        if raw.version != CHAIN_FILE_VERSION {
+            return Err(ParseError::new(0, "version", &format!("chain file version {} isn't supported (expected {})", raw.version, CHAIN_FILE_VERSION)))
+        }
+        let spec = ChainSpec::from_raw(raw.spec).map_err(|e| ParseError::new(e.offset, &format!("spec {}", e.field), &e.reason))?;
+        let mut txs = vec![];
+        for (i, raw_tx) in raw.txs.iter().enumerate() {
+            let tx = parser::decode_from_hex(&raw_tx.hex).map_err(|e| ParseError::new(e.offset, &format!("tx {} {}", i, e.field), &e.reason))?;
+            if tx.txid != Txid::from_hex(&raw_tx.txid)? { return Err(ParseError::new(0, &format!("tx {} txid", i), "doesn't match the transaction")) }
+            txs.push(tx);
+        }
+        return Ok(ChainFile::new(spec, txs))
+    }
+
+    pub fn read(path: &str) -> Result<ChainFile, Box<dyn std::error::Error>> {
+        return Ok(ChainFile::from_json(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn write(&self, path: &str) -> std::io::Result<()> {
+        return std::fs::write(path, self.to_json())
+    }
+}
+
+// Generates the covenant transactions (needs to be pre-calculated and published instead of key)
+pub fn generate_covenant_txs(key_string: &str, spec: &ChainSpec, reps: u64) -> Result<Vec<transaction::Content>, ParseError> {
+    let key = ecc::ECC::new(key_string);
+    let covenant = Covenant::new(&key, spec.covenant_type, spec.csv_delay, spec.dust_limit);
+    let vsize = covenant.vsize;
+    if reps > spec.affordable_steps(vsize) {
+        return Err(ParseError::new(0, "reps", &format!("{} is more than the {} covenant txs the spec allows (its {} sats pay for that many, at most {})",
+            reps, spec.affordable_steps(vsize), spec.input_satoshis, spec.max_steps)))
+    }
+    let (mut input_txid, mut input_vout, mut input_satoshis) = (spec.genesis_txid, spec.genesis_vout, spec.input_satoshis);
+    let mut covenant_txs = vec![];
+    //let script = build_covenant_script(&key.get_pk_bytes(), 1);
+    //let p2sh_address = parser::script_to_p2sh_address(script.as_bytes(), &network::Network::Signet);
+    //println!("{}", p2sh_address); // 2NEcniP26o4oF2jUHgTAcncJnKt653gxrLw
+    for step in 0..reps {
+        let covenant_satoshis = spec.step_cost(step, vsize).and_then(|cost| input_satoshis.checked_sub(cost))
+            .ok_or_else(|| ParseError::new(0, "reps", &format!("the {} sats left at step {} don't pay for its covenant tx", input_satoshis, step)))?;
+        let tx = covenant::build_covenant_tx(&input_txid, input_vout, input_satoshis, covenant_satoshis, &key, &covenant)?;
+        input_txid = tx.txid;
+        input_vout = 0;
+        input_satoshis = tx.outputs[0].value; // the fee can change from step to step
+        covenant_txs.push(tx);
+    }
+    return Ok(covenant_txs)
+}
+
+// The covenant transactions as raw hex, the way they're audited and broadcast
+pub fn generate_covenant_tx_sequence(key_string: &str, spec: &ChainSpec, reps: u64) -> Result<Vec<String>, ParseError> {
+    return Ok(generate_covenant_txs(key_string, spec, reps)?.iter().map(parser::encode_to_hex).collect())
+}
+
+// Signs the chain of a spec and collects it for publishing, the key has to match the spec's pubkey
+pub fn generate_chain_file(key_string: &str, spec: &ChainSpec, reps: u64) -> Result<ChainFile, ParseError> {
+    let key = ecc::ECC::new(key_string);
+    if key.get_pk_string() != spec.pubkey { return Err(ParseError::new(0, "secret key", "doesn't belong to the covenant pubkey")) }
+    return Ok(ChainFile::new(spec.clone(), generate_covenant_txs(key_string, spec, reps)?))
+}
+
+// The json layout of a chain file, the spec comes first so it can be read without scrolling past every tx
+#[derive(Debug, Serialize, Deserialize)]
+struct RawChainFile {
+    version: u32,
+    spec: RawChainSpec,
+    txs: Vec<RawChainTx>
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RawChainTx {
+    txid: String,
+    hex: String
+}
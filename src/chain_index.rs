@@ -0,0 +1,56 @@
+use std::ops::Range;
+use super::transaction::{self, Txid, Amount};
+use super::chain_file::ChainFile;
+
+// Lookups into a loaded chain file, its txids are mapped to steps on load so finding a tx doesn't scan (or re-sign) the whole chain
+// Step n is chain.txs[n], which spends the genesis outpoint for n = 0 and output 0 of step n - 1 otherwise
+pub struct ChainIndex<'a> {
+    chain: &'a ChainFile
+}
+
+impl<'a> ChainIndex<'a> {
+    pub fn new(chain: &'a ChainFile) -> ChainIndex<'a> {
+        return ChainIndex { chain }
+    }
+
+    pub fn len(&self) -> usize {
+        return self.chain.txs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.chain.txs.is_empty()
+    }
+
+    // Which step a txid is (None for the genesis txid, which comes before step 0)
+    pub fn step(&self, txid: &Txid) -> Option<usize> {
+        return self.chain.step(txid)
+    }
+
+    pub fn tx(&self, step: usize) -> Option<&'a transaction::Content> {
+        return self.chain.txs.get(step)
+    }
+
+    // The value of the covenant output a step spends
+    pub fn input_value(&self, step: usize) -> Option<Amount> {
+        if step >= self.len() { return None }
+        return Some(if step == 0 { self.chain.spec.input_satoshis } else { self.chain.txs[step - 1].outputs[0].value })
+    }
+
+    // Gets the covenant tx that spends txid (the genesis outpoint or a tx in the chain) and the value it spends
+    pub fn successor(&self, txid: &Txid) -> Option<(&'a transaction::Content, Amount)> {
+        let step = if *txid == self.chain.spec.genesis_txid { 0 } else { self.step(txid)? + 1 };
+        return Some((self.tx(step)?, self.input_value(step)?))
+    }
+
+    // How many covenant txs are left after txid (None if it isn't in the chain)
+    pub fn remaining(&self, txid: &Txid) -> Option<usize> {
+        if *txid == self.chain.spec.genesis_txid { return Some(self.len()) }
+        return self.step(txid).map(|step| self.len() - step - 1)
+    }
+
+    // The txs of a range of steps, cut off at the end of the chain
+    pub fn range(&self, steps: Range<usize>) -> &'a [transaction::Content] {
+        let end = steps.end.min(self.len());
+        return &self.chain.txs[steps.start.min(end)..end]
+    }
+}
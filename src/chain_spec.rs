@@ -0,0 +1,245 @@
+use serde::{Serialize, Deserialize};
+use super::transaction::{Txid, Amount};
+use super::parser::{self, ParseError};
+use super::network::Network;
+use super::ecc;
+use super::covenant::{CovenantType, Covenant};
+
+// About 19 years of blocks, the chain is signed (and checked) step by step so this also bounds the time that takes
+pub const MAX_STEPS: u64 = 1_000_000;
+
+// Everything that describes one covenant chain, so chains on different networks (or with different amounts) can sit side by side
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChainSpec {
+    pub name: String, // its chain file is <name>_chain.json
+    pub network: Network, pub covenant_type: CovenantType, pub pubkey: String,
+    pub genesis_txid: Txid, pub genesis_vout: u32, pub input_satoshis: Amount,
+    pub dust_limit: Amount, // value of the bump output
+    pub fee: FeeSchedule, // paid by each covenant tx itself
+    pub csv_delay: u16, // blocks between covenant txs, the relative locktime of the covenant script
+    pub max_steps: u64 // how far ahead a chain may be signed
+}
+
+impl ChainSpec {
+    // The signet demo chain (it was regtest "2715afb15d8f92028de0fd98e68e48ee496ac83f5c9dbf031a0a6a21a7e9cd59" with 10*100_000_000 sats)
+    pub fn signet() -> ChainSpec {
+        return ChainSpec {
+            name:            "signet".to_string(),
+            network:         Network::Signet,
+            covenant_type:   CovenantType::P2sh,
+            pubkey:          "03df26767289da117bea582be1aa876ba426a25cbb1ba1d709877aba2d58d9717a".to_string(),
+            genesis_txid:    Txid::from_hex("60c31751818bd4410eed84b1c9047863206cce2c7d4d610ce5841c4195ba6c3b").unwrap(),
+            genesis_vout:    1, // Note this isn't a fixed value
+            input_satoshis:  Amount::from_sat(100_000).unwrap(),
+            dust_limit:      Amount::from_sat(800).unwrap(), // TODO: shave down these numbers (573 for p2sh dust?)
+            fee:             FeeSchedule::constant(Amount::from_sat(1200).unwrap()),
+            csv_delay:       1,
+            max_steps:       52560 // a year of blocks
+        }
+    }
+
+    // Sats the covenant tx of a step takes from the chain (None if that's more than MAX_MONEY)
+    pub fn step_cost(&self, step: u64, vsize: u64) -> Option<Amount> {
+        return self.fee.fee_at(step, vsize)?.checked_add(self.dust_limit)
+    }
+
+    // The scripts and vsize every covenant tx of the chain shares
+    pub fn covenant(&self) -> Result<Covenant, ParseError> {
+        return Ok(Covenant::new(&ecc::ECC::from_pk_string(&self.pubkey)?, self.covenant_type, self.csv_delay, self.dust_limit))
+    }
+
+    // How many covenant txs (of the covenant's vsize) the genesis output can pay for, at most max_steps
+    pub fn affordable_steps(&self, vsize: u64) -> u64 {
+        let (mut steps, mut left) = (0, self.input_satoshis);
+        while steps < self.max_steps {
+            match self.step_cost(steps, vsize).and_then(|cost| left.checked_sub(cost)) {
+                Some(rest) => left = rest,
+                None => break
+            }
+            steps += 1;
+        }
+        return steps
+    }
+
+    pub fn chain_file_path(&self) -> String {
+        return format!("{}_chain.json", self.name)
+    }
+
+    // Checks what the types alone can't, specs are read from files written by hand
+    pub fn validate(&self) -> Result<(), ParseError> {
+        if self.name.is_empty() || !self.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(ParseError::new(0, "name", "has to be letters, digits, _ or - (it becomes a file name)"))
+        }
+        let covenant = self.covenant()?;
+        self.fee.validate()?;
+        if self.max_steps > MAX_STEPS { return Err(ParseError::new(0, "max_steps", &format!("can't be more than {}", MAX_STEPS))) }
+        if self.csv_delay == 0 { return Err(ParseError::new(0, "csv_delay", "has to be at least 1 block, or the whole chain could be mined at once")) }
+        if self.affordable_steps(covenant.vsize) == 0 { return Err(ParseError::new(0, "input_satoshis", "can't pay for a single covenant tx")) }
+        return Ok(())
+    }
+
+    pub fn to_raw(&self) -> RawChainSpec {
+        let (network, signet_challenge) = match &self.network {
+            Network::CustomSignet { challenge } => ("signet".to_string(), Some(hex::encode(challenge))),
+            network => (network.name().to_string(), None)
+        };
+        return RawChainSpec {
+            name:              self.name.clone(),
+            network,
+            signet_challenge,
+            covenant_type:     self.covenant_type.name().to_string(),
+            pubkey:            self.pubkey.clone(),
+            genesis_txid:      self.genesis_txid.to_hex(),
+            genesis_vout:      self.genesis_vout,
+            input_satoshis:    self.input_satoshis.to_sat(),
+            dust_limit:        self.dust_limit.to_sat(),
+            fee:               self.fee.to_raw(),
+            csv_delay:         self.csv_delay,
+            max_steps:         self.max_steps
+        }
+    }
+
+    pub fn from_raw(raw: RawChainSpec) -> Result<ChainSpec, ParseError> {
+        let network = match (Network::from_name(&raw.network), raw.signet_challenge) {
+            (Some(Network::Signet), Some(challenge)) => Network::CustomSignet { challenge: parser::hex_to_bytes(&challenge, "signet_challenge")? },
+            (Some(network), None) => network,
+            _ => return Err(ParseError::new(0, "network", &format!("unknown network {}", raw.network)))
+        };
+        let covenant_type = CovenantType::from_name(&raw.covenant_type)
+            .ok_or_else(|| ParseError::new(0, "covenant_type", &format!("unknown covenant type {}", raw.covenant_type)))?;
+        let amount = |satoshis: u64, field: &str| Amount::from_sat(satoshis).ok_or_else(|| ParseError::new(0, field, "exceeds MAX_MONEY"));
+        let spec = ChainSpec {
+            name:            raw.name,
+            network,
+            covenant_type,
+            pubkey:          raw.pubkey,
+            genesis_txid:    Txid::from_hex(&raw.genesis_txid)?,
+            genesis_vout:    raw.genesis_vout,
+            input_satoshis:  amount(raw.input_satoshis, "input_satoshis")?,
+            dust_limit:      amount(raw.dust_limit, "dust_limit")?,
+            fee:             FeeSchedule::from_raw(raw.fee)?,
+            csv_delay:       raw.csv_delay,
+            max_steps:       raw.max_steps
+        };
+        spec.validate()?;
+        return Ok(spec)
+    }
+
+    pub fn to_json(&self) -> String {
+        return serde_json::to_string_pretty(&self.to_raw()).unwrap()
+    }
+
+    pub fn from_json(json: &str) -> Result<ChainSpec, ParseError> {
+        return ChainSpec::from_raw(serde_json::from_str(json).map_err(|e| ParseError::new(0, "json", &e.to_string()))?)
+    }
+
+    pub fn read(path: &str) -> Result<ChainSpec, Box<dyn std::error::Error>> {
+        return Ok(ChainSpec::from_json(&std::fs::read_to_string(path)?)?)
+    }
+}
+
+// The json layout of a spec, on its own or as the header of a chain file
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)] // a misspelled field would otherwise silently fall back to nothing
+pub struct RawChainSpec {
+    name: String,
+    network: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    signet_challenge: Option<String>,
+    covenant_type: String,
+    pubkey: String,
+    genesis_txid: String,
+    genesis_vout: u32,
+    input_satoshis: u64,
+    dust_limit: u64,
+    fee: RawFeeSchedule,
+    csv_delay: u16,
+    max_steps: u64
+}
+
+// What a covenant tx pays, from a given step on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fee {
+    Constant(Amount),
+    Rate(u64) // sats per 1000 vbytes of the covenant tx, like Core's -minrelaytxfee
+}
+
+// The fee of each step, so a long-lived chain can pay more later on (e.g. when the minimum relay fee rises)
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeeSchedule {
+    pub tiers: Vec<(u64, Fee)> // (first step, fee), the first tier starts at step 0
+}
+
+impl FeeSchedule {
+    pub fn constant(fee: Amount) -> FeeSchedule {
+        return FeeSchedule { tiers: vec![(0, Fee::Constant(fee))] }
+    }
+
+    pub fn rate(sat_per_kvb: u64) -> FeeSchedule {
+        return FeeSchedule { tiers: vec![(0, Fee::Rate(sat_per_kvb))] }
+    }
+
+    // The fee of a step for a covenant tx of vsize vbytes (rates round up, None if it's more than MAX_MONEY)
+    pub fn fee_at(&self, step: u64, vsize: u64) -> Option<Amount> {
+        let (_, fee) = self.tiers.iter().rev().find(|(from_step, _)| *from_step <= step)?;
+        return match fee {
+            Fee::Constant(fee) => Some(*fee),
+            Fee::Rate(sat_per_kvb) => Amount::from_sat(vsize.checked_mul(*sat_per_kvb)?.div_ceil(1000))
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), ParseError> {
+        if self.tiers.first().map(|(from_step, _)| *from_step) != Some(0) { return Err(ParseError::new(0, "fee", "the first tier has to start at step 0")) }
+        if self.tiers.windows(2).any(|tiers| tiers[0].0 >= tiers[1].0) { return Err(ParseError::new(0, "fee", "tiers have to start at increasing steps")) }
+        return Ok(())
+    }
+
+    pub fn to_raw(&self) -> RawFeeSchedule {
+        return match self.tiers.as_slice() {
+            [(0, Fee::Constant(fee))] => RawFeeSchedule::Constant(fee.to_sat()),
+            tiers => RawFeeSchedule::Tiers(tiers.iter().map(|(from_step, fee)| match fee {
+                Fee::Constant(fee) => RawFeeTier { from_step: *from_step, sats: Some(fee.to_sat()), sat_per_kvb: None },
+                Fee::Rate(sat_per_kvb) => RawFeeTier { from_step: *from_step, sats: None, sat_per_kvb: Some(*sat_per_kvb) }
+            }).collect())
+        }
+    }
+
+    pub fn from_raw(raw: RawFeeSchedule) -> Result<FeeSchedule, ParseError> {
+        let amount = |satoshis: u64| Amount::from_sat(satoshis).ok_or_else(|| ParseError::new(0, "fee", "exceeds MAX_MONEY"));
+        let schedule = match raw {
+            RawFeeSchedule::Constant(fee) => FeeSchedule::constant(amount(fee)?),
+            RawFeeSchedule::Tiers(tiers) => {
+                let mut schedule = FeeSchedule { tiers: vec![] };
+                for tier in tiers {
+                    let fee = match (tier.sats, tier.sat_per_kvb) {
+                        (Some(fee), None) => Fee::Constant(amount(fee)?),
+                        (None, Some(sat_per_kvb)) => Fee::Rate(sat_per_kvb),
+                        _ => return Err(ParseError::new(0, "fee", &format!("the tier from step {} needs either sats or sat_per_kvb", tier.from_step)))
+                    };
+                    schedule.tiers.push((tier.from_step, fee));
+                }
+                schedule
+            }
+        };
+        schedule.validate()?;
+        return Ok(schedule)
+    }
+}
+
+// A plain number is a constant fee, otherwise a list of tiers like [{"from_step": 0, "sats": 1200}, {"from_step": 26280, "sat_per_kvb": 5000}]
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RawFeeSchedule {
+    Constant(u64),
+    Tiers(Vec<RawFeeTier>)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RawFeeTier {
+    from_step: u64,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    sats: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    sat_per_kvb: Option<u64>
+}
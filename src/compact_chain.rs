@@ -0,0 +1,142 @@
+use super::transaction::{self, Txid, Amount};
+use super::parser::{ParseError, Bytestream, ByteWriter, Instruction};
+use super::network::Network;
+use super::ecc;
+use super::audit;
+use super::chain_file::ChainFile;
+use super::chain_spec::{ChainSpec, FeeSchedule, Fee};
+use super::covenant::{self, CovenantType};
+
+// "spacechain sigs" followed by the format version
+const MAGIC: &[u8; 4] = b"scsg";
+pub const COMPACT_CHAIN_VERSION: u8 = 1;
+
+// A chain file without the txs, only the signature of every step (about 74 bytes per p2sh tx instead of 260 as hex)
+// The txs are fully determined by the spec in the header, so they are rebuilt from it, layout:
+// magic, version, spec name, network name, signet challenge (empty if none), covenant type, pubkey, genesis txid and vout,
+// input_satoshis, dust_limit, fee tiers (count, then first step, 0 for sats or 1 for sat/kvB and the value of each),
+// csv_delay, max_steps, number of steps, then a length prefixed signature per step
+pub fn encode(chain: &ChainFile) -> Vec<u8> {
+    let spec = &chain.spec;
+    let mut w = ByteWriter::new();
+    w.write_bytes(MAGIC);
+    w.write_int(COMPACT_CHAIN_VERSION as u64, 1);
+    let (network, signet_challenge) = match &spec.network {
+        Network::CustomSignet { challenge } => ("signet", challenge.clone()),
+        network => (network.name(), vec![])
+    };
+    w.write_length_prefixed(spec.name.as_bytes());
+    w.write_length_prefixed(network.as_bytes());
+    w.write_length_prefixed(&signet_challenge);
+    w.write_length_prefixed(spec.covenant_type.name().as_bytes());
+    w.write_length_prefixed(&hex::decode(&spec.pubkey).unwrap());
+    w.write_bytes(spec.genesis_txid.as_bytes());
+    w.write_int(spec.genesis_vout as u64, 4);
+    w.write_int(spec.input_satoshis.to_sat(), 8);
+    w.write_int(spec.dust_limit.to_sat(), 8);
+    w.write_varint(spec.fee.tiers.len() as u64);
+    for (from_step, fee) in &spec.fee.tiers {
+        w.write_varint(*from_step);
+        match fee {
+            Fee::Constant(fee) => { w.write_int(0, 1); w.write_int(fee.to_sat(), 8) }
+            Fee::Rate(sat_per_kvb) => { w.write_int(1, 1); w.write_int(*sat_per_kvb, 8) }
+        }
+    }
+    w.write_int(spec.csv_delay as u64, 2);
+    w.write_int(spec.max_steps, 8);
+    w.write_varint(chain.txs.len() as u64);
+    for tx in &chain.txs { w.write_length_prefixed(&covenant_signature(tx, spec.covenant_type)) }
+    return w.into_bytes()
+}
+
+// Rebuilds every covenant tx from the header and its signature, without checking the signatures (see reconstruct)
+pub fn decode(bytes: &[u8]) -> Result<ChainFile, ParseError> {
+    let mut stream = Bytestream::new(bytes);
+    if stream.get_bytes(4, "magic")? != MAGIC { return Err(ParseError::new(0, "magic", "not a compact covenant chain")) }
+    let version = stream.get_int(1, "version")?;
+    if version != COMPACT_CHAIN_VERSION as u64 {
+        return Err(ParseError::new(4, "version", &format!("compact chain version {} isn't supported (expected {})", version, COMPACT_CHAIN_VERSION)))
+    }
+    let name = |stream: &mut Bytestream, field: &str| -> Result<String, ParseError> {
+        let offset = stream.offset();
+        let bytes = stream.get_length_prefixed(field)?;
+        return String::from_utf8(bytes.to_vec()).map_err(|_| ParseError::new(offset, field, "isn't utf-8"))
+    };
+
+    let spec_name = name(&mut stream, "name")?;
+    let offset = stream.offset();
+    let network_name = name(&mut stream, "network")?;
+    let signet_challenge = stream.get_length_prefixed("signet challenge")?;
+    let network = match (Network::from_name(&network_name), signet_challenge.is_empty()) {
+        (Some(Network::Signet), false) => Network::CustomSignet { challenge: signet_challenge.to_vec() },
+        (Some(network), true) => network,
+        _ => return Err(ParseError::new(offset, "network", &format!("unknown network {}", network_name)))
+    };
+    let offset = stream.offset();
+    let covenant_type_name = name(&mut stream, "covenant type")?;
+    let covenant_type = CovenantType::from_name(&covenant_type_name)
+        .ok_or_else(|| ParseError::new(offset, "covenant type", &format!("unknown covenant type {}", covenant_type_name)))?;
+    let offset = stream.offset();
+    let key = ecc::ECC::from_pk_bytes(stream.get_length_prefixed("pubkey")?).map_err(|e| ParseError::new(offset, &e.field, &e.reason))?;
+    let genesis_txid = Txid::from_bytes(stream.get_hash("genesis txid")?);
+    let genesis_vout = stream.get_int(4, "genesis vout")? as u32;
+    let amount = |stream: &mut Bytestream, field: &str| {
+        let offset = stream.offset();
+        return stream.get_int(8, field).and_then(|satoshis| Amount::from_sat(satoshis).ok_or_else(|| ParseError::new(offset, field, "exceeds MAX_MONEY")))
+    };
+    let input_satoshis = amount(&mut stream, "input_satoshis")?;
+    let dust_limit = amount(&mut stream, "dust_limit")?;
+    let mut fee = FeeSchedule { tiers: vec![] };
+    for _ in 0..stream.get_varint("fee tiers")? {
+        let from_step = stream.get_varint("fee tier step")?;
+        let offset = stream.offset();
+        let tier = match stream.get_int(1, "fee tier type")? {
+            0 => Fee::Constant(amount(&mut stream, "fee")?),
+            1 => Fee::Rate(stream.get_int(8, "fee")?),
+            _ => return Err(ParseError::new(offset, "fee tier type", "has to be 0 (sats) or 1 (sat/kvB)"))
+        };
+        fee.tiers.push((from_step, tier));
+    }
+    let csv_delay = stream.get_int(2, "csv_delay")? as u16;
+    let max_steps = stream.get_int(8, "max_steps")?;
+    let spec = ChainSpec {
+        name: spec_name, network, covenant_type, pubkey: key.get_pk_string(), genesis_txid, genesis_vout, input_satoshis, dust_limit, fee, csv_delay, max_steps
+    };
+    let offset = stream.offset();
+    spec.validate().map_err(|e| ParseError::new(offset, &e.field, &e.reason))?;
+    let steps = stream.get_varint("steps")?;
+    // also keeps the builder from running out of sats to take the cost from
+    let covenant = spec.covenant().map_err(|e| ParseError::new(offset, &e.field, &e.reason))?;
+    let vsize = covenant.vsize;
+    if steps > spec.affordable_steps(vsize) { return Err(ParseError::new(offset, "steps", &format!("{} is more than the spec allows ({})", steps, spec.affordable_steps(vsize)))) }
+    let (mut txs, mut spent_txid, mut spent_vout, mut spent_satoshis) = (vec![], genesis_txid, genesis_vout, input_satoshis);
+    for step in 0..steps {
+        let sig = stream.get_length_prefixed(&format!("step {} signature", step))?;
+        let covenant_satoshis = spent_satoshis.checked_sub(spec.step_cost(step, vsize).unwrap()).unwrap(); // affordable, checked above
+        let mut tx = covenant::build_unsigned_covenant_tx(&spent_txid, spent_vout, covenant_satoshis, &covenant);
+        covenant::add_covenant_signature(&mut tx, sig.to_vec(), &covenant);
+        (spent_txid, spent_vout, spent_satoshis) = (tx.txid, 0, tx.outputs[0].value);
+        txs.push(tx);
+    }
+    stream.expect_end("compact chain")?;
+
+    return Ok(ChainFile::new(spec, txs))
+}
+
+// Rebuilds the chain and audits it, so every signature is checked against the covenant pubkey in the header
+pub fn reconstruct(bytes: &[u8]) -> Result<ChainFile, Box<dyn std::error::Error>> {
+    let chain = decode(bytes)?;
+    audit::audit_covenant_tx_sequence(&chain.spec, &chain.tx_strings())?;
+    return Ok(chain)
+}
+
+// The signature is the first push of the p2sh scriptsig, or the first witness item
+fn covenant_signature(tx: &transaction::Content, covenant_type: CovenantType) -> Vec<u8> {
+    return match covenant_type {
+        CovenantType::P2sh => match tx.inputs[0].scriptsig.instructions().ok().and_then(|instructions| instructions.into_iter().next()) {
+            Some(Instruction::Push(sig)) => sig,
+            _ => vec![]
+        },
+        _ => tx.inputs[0].witness.first().cloned().unwrap_or_default()
+    }
+}
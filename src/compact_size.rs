@@ -0,0 +1,48 @@
+use super::{Bytestream, ParseError};
+
+// CompactSize is how counts and lengths are serialized: values below 0xfd take a single byte,
+// bigger values get a 0xfd, 0xfe or 0xff marker followed by 2, 4 or 8 little endian bytes
+
+// Core refuses to read sizes above this (32 MiB), nothing valid comes close
+pub const MAX_SIZE: u64 = 0x02000000;
+
+// Number of bytes the encoding of a value takes
+pub fn encoded_len(value: u64) -> usize {
+    return match value {
+        0..=0xfc => 1,
+        0xfd..=0xffff => 3,
+        0x10000..=0xffffffff => 5,
+        _ => 9
+    }
+}
+
+// Always uses the shortest encoding
+pub fn encode(value: u64) -> Vec<u8> {
+    let (marker, size) = match encoded_len(value) {
+        1 => return vec![value as u8],
+        3 => (0xfd, 2),
+        5 => (0xfe, 4),
+        _ => (0xff, 8)
+    };
+    return [&[marker][..], &value.to_le_bytes()[..size]].concat()
+}
+
+// Reads a value, rejecting encodings that aren't the shortest possible (Core's "non-canonical ReadCompactSize()")
+// and values above MAX_SIZE
+pub fn decode(stream: &mut Bytestream, field: &str) -> Result<u64, ParseError> {
+    let offset = stream.offset();
+    let marker = stream.get_int(1, field)?;
+    let value = match marker {
+        0xfd => stream.get_int(2, field)?,
+        0xfe => stream.get_int(4, field)?,
+        0xff => stream.get_int(8, field)?,
+        _ => return Ok(marker)
+    };
+    if encoded_len(value) != stream.offset() - offset {
+        return Err(ParseError::new(offset, field, &format!("non-canonical compact size for {}", value)))
+    }
+    if value > MAX_SIZE {
+        return Err(ParseError::new(offset, field, &format!("compact size {} is larger than {}", value, MAX_SIZE)))
+    }
+    return Ok(value)
+}
@@ -1,41 +1,189 @@
+use serde::{Serialize, Deserialize};
 use serde_json::{Value};
 use super::transaction;
+use super::parser::{self, ParseError, Script, Instruction, Opcode};
+use super::address;
+use super::network::Network;
 
 // Runs the relevant rpc command and parses it
-pub fn decoderawtransaction(transaction_hex_string: &str) -> transaction::Content {
+pub fn decoderawtransaction(transaction_hex_string: &str) -> Result<transaction::Content, ParseError> {
     let rpc_tx = bitcoin_cli(vec!["decoderawtransaction", transaction_hex_string]);
-    let rpc_inputs = rpc_tx["vin"].as_array().unwrap();
-    let rpc_outputs = rpc_tx["vout"].as_array().unwrap();
-
-    let (mut inputs, mut outputs) = (vec![], vec![]);
-
-    for i in 0..rpc_inputs.len() {
-        let i = &rpc_inputs[i];
-        let input = transaction::Input {
-            txid:       i["txid"].as_str().unwrap().to_string(),
-            vout:       i["vout"].as_u64().unwrap(),
-            scriptsig:  i["scriptSig"]["hex"].as_str().unwrap().to_string(),
-            sequence:   i["sequence"].as_u64().unwrap()
-        };
-        inputs.push(input);
+    let raw_tx = RawTransaction::deserialize(rpc_tx).map_err(|e| ParseError::new(0, "json", &e.to_string()))?;
+    return raw_tx.to_tx()
+}
+
+// Turns a transaction object into the json Core's decoderawtransaction shows (except "desc", as descriptors aren't supported)
+pub fn tx_to_json(tx: &transaction::Content, network: &Network) -> String {
+    return serde_json::to_string_pretty(&RawTransaction::from_tx(tx, network)).unwrap()
+}
+
+// Turns json in the shape of Core's decoderawtransaction into a transaction object
+// Only the fields needed to rebuild the tx are read, and the txid is checked if there is one
+pub fn tx_from_json(json: &str) -> Result<transaction::Content, ParseError> {
+    let raw_tx: RawTransaction = serde_json::from_str(json).map_err(|e| ParseError::new(0, "json", &e.to_string()))?;
+    return raw_tx.to_tx()
+}
+
+// Core's decoderawtransaction json, field names and order match Core
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RawTransaction {
+    #[serde(default)]
+    pub txid: String,
+    #[serde(skip_deserializing)]
+    pub hash: String,
+    pub version: u32,
+    #[serde(skip_deserializing)]
+    pub size: u64,
+    #[serde(skip_deserializing)]
+    pub vsize: u64,
+    #[serde(skip_deserializing)]
+    pub weight: u64,
+    pub locktime: u32,
+    pub vin: Vec<RawInput>,
+    pub vout: Vec<RawOutput>
+}
+
+// A coinbase input only has the coinbase (its scriptsig in hex) instead of txid, vout and scriptSig
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RawInput {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub coinbase: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub txid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vout: Option<u32>,
+    #[serde(rename = "scriptSig", skip_serializing_if = "Option::is_none")]
+    pub script_sig: Option<RawScriptSig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub txinwitness: Option<Vec<String>>,
+    pub sequence: u32
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RawScriptSig {
+    #[serde(default)]
+    pub asm: String,
+    pub hex: String
+}
+
+// Values are in bitcoin, like in Core
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RawOutput {
+    pub value: f64,
+    pub n: u32,
+    #[serde(rename = "scriptPubKey")]
+    pub script_pubkey: RawScriptPubKey
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RawScriptPubKey {
+    #[serde(default)]
+    pub asm: String,
+    pub hex: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    #[serde(rename = "type", default)]
+    pub script_type: String
+}
+
+impl RawTransaction {
+    // Addresses depend on the network, everything else is the same for every network
+    pub fn from_tx(tx: &transaction::Content, network: &Network) -> RawTransaction {
+        let vin = tx.inputs.iter().map(|i| {
+            let txinwitness = if i.witness.is_empty() { None } else { Some(i.witness.iter().map(hex::encode).collect()) };
+            if tx.is_coinbase() {
+                return RawInput { coinbase: Some(i.scriptsig.to_hex()), txid: None, vout: None, script_sig: None, txinwitness, sequence: i.sequence.0 }
+            }
+            return RawInput {
+                coinbase:     None,
+                txid:         Some(i.txid.to_hex()),
+                vout:         Some(i.vout),
+                script_sig:   Some(RawScriptSig { asm: i.scriptsig.to_scriptsig_asm(), hex: i.scriptsig.to_hex() }),
+                txinwitness,
+                sequence:     i.sequence.0
+            }
+        }).collect();
+        let vout = tx.outputs.iter().enumerate().map(|(n, o)| RawOutput {
+            value:          o.value.to_sat() as f64 / 100_000_000.0,
+            n:              n as u32,
+            script_pubkey:  RawScriptPubKey {
+                asm:          o.scriptpubkey.to_asm(),
+                hex:          o.scriptpubkey.to_hex(),
+                address:      address::scriptpubkey_to_address(o.scriptpubkey.as_bytes(), network),
+                script_type:  script_type(&o.scriptpubkey).to_string()
+            }
+        }).collect();
+        return RawTransaction {
+            txid:      tx.txid.to_hex(),
+            hash:      parser::txid_to_hex(&parser::tx_to_wtxid(tx)),
+            version:   tx.version,
+            size:      parser::encode(tx).len() as u64,
+            vsize:     parser::get_vsize(tx),
+            weight:    parser::get_weight(tx),
+            locktime:  tx.locktime.0,
+            vin,
+            vout
+        }
     }
 
-    for i in 0..rpc_outputs.len() {
-        let i = &rpc_outputs[i];
-        let output = transaction::Output {
-            value:         (i["value"].as_f64().unwrap()*100_000_000.0) as u64,
-            scriptpubkey:   i["scriptPubKey"]["hex"].as_str().unwrap().to_string()
-        };
-        outputs.push(output);
+    pub fn to_tx(&self) -> Result<transaction::Content, ParseError> {
+        let mut inputs = vec![];
+        for i in &self.vin {
+            let witness = match &i.txinwitness {
+                Some(items) => items.iter().map(|item| parser::hex_to_bytes(item, "txinwitness")).collect::<Result<_, _>>()?,
+                None => vec![]
+            };
+            let (txid, vout, scriptsig) = match (&i.coinbase, &i.txid, i.vout, &i.script_sig) {
+                (Some(coinbase), None, None, None) => (transaction::Txid::default(), u32::MAX, Script::from_hex(coinbase)?),
+                (None, Some(txid), Some(vout), Some(script_sig)) => (transaction::Txid::from_hex(txid)?, vout, Script::from_hex(&script_sig.hex)?),
+                _ => return Err(ParseError::new(0, "vin", "needs either coinbase or txid, vout and scriptSig"))
+            };
+            inputs.push(transaction::Input { txid, vout, scriptsig, sequence: transaction::Sequence(i.sequence), witness });
+        }
+        let mut outputs = vec![];
+        for o in &self.vout {
+            let value = transaction::Amount::from_btc(o.value).ok_or_else(|| ParseError::new(0, "vout value", &format!("{} is not a valid amount", o.value)))?;
+            outputs.push(transaction::Output { value, scriptpubkey: Script::from_hex(&o.script_pubkey.hex)? });
+        }
+        let mut tx = transaction::Content { txid: transaction::Txid::default(), version: self.version, locktime: transaction::LockTime(self.locktime), inputs, outputs };
+        tx.txid = parser::tx_to_txid(&tx);
+        if !self.txid.is_empty() && transaction::Txid::from_hex(&self.txid)? != tx.txid {
+            return Err(ParseError::new(0, "txid", "doesn't match the transaction"))
+        }
+        return Ok(tx)
     }
+}
 
-    return transaction::Content {
-        txid:       rpc_tx["txid"].as_str().unwrap().to_string(),
-        version:    rpc_tx["version"].as_u64().unwrap(), 
-        locktime:   rpc_tx["locktime"].as_u64().unwrap(), 
-        inputs,
-        outputs
+// Classifies an output script with the type names Core uses
+pub fn script_type(script: &Script) -> &'static str {
+    let s = script.as_bytes();
+    if s == [0x51, 0x02, 0x4e, 0x73] { return "anchor" }
+    if s.len() == 23 && s[..2] == [0xa9, 0x14] && s[22] == 0x87 { return "scripthash" }
+    let is_version_opcode = s.first().is_some_and(|op| *op == 0x00 || (0x51..=0x60).contains(op));
+    if is_version_opcode && s.len() >= 4 && s.len() <= 42 && s[1] as usize == s.len() - 2 {
+        return match (s[0], s.len() - 2) {
+            (0x00, 20) => "witness_v0_keyhash",
+            (0x00, 32) => "witness_v0_scripthash",
+            (0x00, _) => "nonstandard",
+            (0x51, 32) => "witness_v1_taproot",
+            _ => "witness_unknown"
+        }
     }
+    let instructions = match script.instructions() { Ok(instructions) => instructions, Err(_) => return "nonstandard" };
+    let is_push = |instruction: &Instruction| matches!(instruction, Instruction::Push(_)) || matches!(instruction, Instruction::Op(op) if (*op as u8) <= Opcode::OP_16 as u8);
+    if instructions.first() == Some(&Instruction::Op(Opcode::OP_RETURN)) && instructions[1..].iter().all(is_push) { return "nulldata" }
+    let is_pubkey = |instruction: &Instruction| matches!(instruction, Instruction::Push(data) if data.len() == 33 || data.len() == 65);
+    if instructions.len() == 2 && is_pubkey(&instructions[0]) && instructions[1] == Instruction::Op(Opcode::OP_CHECKSIG) { return "pubkey" }
+    if s.len() == 25 && s[..3] == [0x76, 0xa9, 0x14] && s[23..] == [0x88, 0xac] { return "pubkeyhash" }
+    // OP_m <pubkey>... OP_n OP_CHECKMULTISIG
+    let small_number = |instruction: &Instruction| match instruction { Instruction::Op(op) if (0x51..=0x60).contains(&(*op as u8)) => Some(*op as usize - 0x50), _ => None };
+    if instructions.len() >= 4 && instructions[instructions.len()-1] == Instruction::Op(Opcode::OP_CHECKMULTISIG) {
+        let keys = &instructions[1..instructions.len()-2];
+        if let (Some(m), Some(n)) = (small_number(&instructions[0]), small_number(&instructions[instructions.len()-2])) {
+            if m <= n && n == keys.len() && keys.iter().all(is_pubkey) { return "multisig" }
+        }
+    }
+    return "nonstandard"
 }
 
 // Run bitcoin_cli rpc commands (requires bitcoind running)
@@ -48,4 +196,4 @@ fn bitcoin_cli(args: Vec<&str>) -> Value {
     let json = &String::from_utf8_lossy(&output.stdout);
     let parsed_output: Value = serde_json::from_str(json).unwrap();
     return parsed_output
-}
\ No newline at end of file
+}
@@ -0,0 +1,224 @@
+use super::transaction::{self, Txid, Amount, Sequence, LockTime};
+use super::parser::{self, Script, ParseError};
+use super::ecc;
+
+// How the covenant and bump outputs are locked
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CovenantType {
+    P2sh, // the signet chain, scripts are revealed in the scriptsig
+    P2wsh, // scripts are revealed in the witness, so the txids of the chain can't be malleated
+    P2tr // single tapscript leaf with an unspendable internal key, signed with schnorr
+}
+
+impl CovenantType {
+    pub fn name(self) -> &'static str {
+        return match self {
+            CovenantType::P2sh => "p2sh",
+            CovenantType::P2wsh => "p2wsh",
+            CovenantType::P2tr => "p2tr"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<CovenantType> {
+        return match name {
+            "p2sh" => Some(CovenantType::P2sh),
+            "p2wsh" => Some(CovenantType::P2wsh),
+            "p2tr" => Some(CovenantType::P2tr),
+            _ => None
+        }
+    }
+}
+
+// What every covenant tx of a chain has in common, only the spent outpoint, the amounts and the signature change
+#[derive(Debug, Clone, PartialEq)]
+pub struct Covenant {
+    pub covenant_type: CovenantType, pub csv_delay: u16,
+    pub dust_limit: Amount, // value of the bump output
+    pub script: Script, // <pubkey> OP_CHECKSIGVERIFY <csv_delay> OP_CSV
+    pub output: Script, pub bump_output: Script, // the output scripts of the covenant and bump outputs
+    pub vsize: u64 // the same for every covenant tx of the chain, so it's estimated once
+}
+
+impl Covenant {
+    // Only the public key of key is used
+    pub fn new(key: &ecc::ECC, covenant_type: CovenantType, csv_delay: u16, dust_limit: Amount) -> Covenant {
+        let script = match covenant_type {
+            CovenantType::P2tr => build_covenant_script(&key.get_xonly_pk_bytes(), csv_delay), // tapscript takes x-only keys
+            _ => build_covenant_script(&key.get_pk_bytes(), csv_delay)
+        };
+        let output = covenant_output_script(&script, covenant_type);
+        let bump_output = covenant_output_script(&build_bump_script(), covenant_type);
+        let mut covenant = Covenant { covenant_type, csv_delay, dust_limit, script, output, bump_output, vsize: 0 };
+        covenant.vsize = estimate_covenant_vsize(&covenant);
+        return covenant
+    }
+}
+
+// Turns a covenant or bump script into the matching output script
+pub fn covenant_output_script(script: &Script, covenant_type: CovenantType) -> Script {
+    return Script::new(match covenant_type {
+        CovenantType::P2sh => parser::script_to_p2sh_script(script.as_bytes()),
+        CovenantType::P2wsh => parser::script_to_p2wsh_script(script.as_bytes()),
+        CovenantType::P2tr => build_taproot_leaf(script).0
+    })
+}
+
+// BIP341's NUMS point, nobody knows its private key so only the script path can be used
+pub const UNSPENDABLE_INTERNAL_KEY: [u8; 32] = [
+    0x50, 0x92, 0x9b, 0x74, 0xc1, 0xa0, 0x49, 0x54, 0xb7, 0x8b, 0x4b, 0x60, 0x35, 0xe9, 0x7a, 0x5e,
+    0x07, 0x8a, 0x5a, 0x0f, 0x28, 0xec, 0x96, 0xd5, 0x47, 0xbf, 0xee, 0x9a, 0xce, 0x80, 0x3a, 0xc0
+];
+
+// Gets the p2tr output script and the control block for a tree with the script as its only leaf
+pub fn build_taproot_leaf(script: &Script) -> (Vec<u8>, Vec<u8>) {
+    let merkle_root = parser::get_tapleaf_hash(script.as_bytes());
+    let (output_key, odd) = ecc::get_taproot_output_key(&UNSPENDABLE_INTERNAL_KEY, &merkle_root);
+    let control_block = [&[parser::TAPROOT_LEAF_VERSION | odd as u8][..], &UNSPENDABLE_INTERNAL_KEY].concat();
+    return (parser::script_to_p2tr_script(&output_key), control_block)
+}
+
+// The main bmm script that's in use
+pub fn build_covenant_script(pubkey: &[u8], csv_delay: u16) -> Script {
+    return parser::Builder::new()
+        .push_bytes(pubkey)
+        .push_opcode(parser::Opcode::OP_CHECKSIGVERIFY)
+        .push_int(csv_delay as i64)
+        .push_opcode(parser::Opcode::OP_CHECKSEQUENCEVERIFY)
+        .into_script()
+} // <Pubkey> OP_CHECKSIGVERIFY <csv_delay> OP_CSV (1 on signet)
+
+pub fn build_bump_script() -> Script {
+    return parser::Builder::new()
+        .push_int(0)
+        .push_opcode(parser::Opcode::OP_CHECKSEQUENCEVERIFY)
+        .push_opcode(parser::Opcode::OP_1ADD)
+        .into_script()
+} // 0 OP_CSV OP_1ADD (forces RBF)
+
+// Generates the covenant tx (note, input and output are assumed to have the same script, even the 1st input)
+pub fn build_covenant_tx(input_txid: &Txid, input_vout: u32, input_satoshis: Amount, covenant_satoshis: Amount, key: &ecc::ECC, covenant: &Covenant) -> Result<transaction::Content, ParseError> {
+    let mut tx = build_unsigned_covenant_tx(input_txid, input_vout, covenant_satoshis, covenant);
+    let covenant_script = &covenant.script;
+    // TODO: signing should not be done inside this function
+    let sig = match covenant.covenant_type {
+        CovenantType::P2sh => {
+            let sighash_all_data = parser::get_sighash_all_data(&tx, 0, covenant_script.as_bytes()); // Note: NOT p2sh_script
+            let mut sig = key.sign_ecdsa_der(&sighash_all_data)?;
+            sig.push(0x01); // sighash flag needs to be added
+            sig
+        }
+        CovenantType::P2wsh => {
+            let sighash = parser::get_segwit_v0_sighash(&tx, 0, covenant_script.as_bytes(), input_satoshis.to_sat(), parser::SIGHASH_ALL);
+            let mut sig = key.sign_ecdsa_hash_der(&sighash)?;
+            sig.push(0x01); // sighash flag needs to be added
+            sig
+        }
+        CovenantType::P2tr => {
+            let prevout = transaction::Output { value: input_satoshis, scriptpubkey: covenant.output.clone() };
+            let leaf_hash = parser::get_tapleaf_hash(covenant_script.as_bytes());
+            let sighash = parser::get_taproot_sighash(&tx, 0, &[prevout], Some(&leaf_hash), parser::SIGHASH_DEFAULT)
+                .ok_or_else(|| ParseError::new(0, "taproot sighash", "couldn't be computed for the covenant input"))?;
+            key.sign_schnorr_hash(&sighash)?.to_vec() // no sighash flag for SIGHASH_DEFAULT
+        }
+    };
+    add_covenant_signature(&mut tx, sig, covenant);
+
+    return Ok(tx)
+}
+
+// Everything of a covenant tx except its signature, which is all a chain needs to publish per step (only the public key is used)
+// The covenant output gets what's left after the step's fee and the dust of the bump output
+pub fn build_unsigned_covenant_tx(input_txid: &Txid, input_vout: u32, covenant_satoshis: Amount, covenant: &Covenant) -> transaction::Content {
+    let (mut inputs, mut outputs) = (vec![], vec![]);
+
+    inputs.push(transaction::Input {
+        txid:       *input_txid,
+        vout:       input_vout,
+        scriptsig:  Script::default(), // will be sig + covenant_script (in the witness for p2wsh and p2tr)
+        sequence:   Sequence(covenant.csv_delay as u32), // matches script relative locktime (in blocks)
+        witness:    vec![]
+    });
+
+    outputs.push(transaction::Output {
+        value:         covenant_satoshis,
+        scriptpubkey:  covenant.output.clone()
+    });
+
+    outputs.push(transaction::Output {
+        value:         covenant.dust_limit,
+        scriptpubkey:  covenant.bump_output.clone()
+    });
+
+    //let p2sh_address = parser::script_to_p2sh_address(build_bump_script().as_bytes(), &network::Network::Signet);
+    //println!("{p2sh_address}"); // 2MzHTWrk6TpuPAauCaWcPNpEs4Q9VYW6iCQ
+
+    return transaction::Content {
+        txid:       Txid::default(), // calculated after sig is obtained
+        version:    2, // needs to be >1 for op_csv
+        locktime:   LockTime::ZERO,
+        inputs,
+        outputs
+    }
+}
+
+// The vsize of a covenant's txs, assuming the largest signature so a fee rate is never undershot
+// Amounts and outpoints don't change the size, so this holds for every step of the chain
+pub fn estimate_covenant_vsize(covenant: &Covenant) -> u64 {
+    let mut tx = build_unsigned_covenant_tx(&Txid::default(), 0, Amount::from_sat(0).unwrap(), covenant);
+    let largest_sig = match covenant.covenant_type {
+        CovenantType::P2tr => vec![0; 64], // SIGHASH_DEFAULT
+        _ => vec![0; 73] // DER with 33 byte r and s, plus the sighash byte
+    };
+    add_covenant_signature(&mut tx, largest_sig, covenant);
+    return parser::get_vsize(&tx)
+}
+
+// Puts the signature where the covenant type expects it and calculates the txid
+pub fn add_covenant_signature(tx: &mut transaction::Content, sig: Vec<u8>, covenant: &Covenant) {
+    let covenant_script = covenant.script.clone();
+    match covenant.covenant_type {
+        CovenantType::P2sh => tx.inputs[0].scriptsig = parser::Builder::new().push_bytes(&sig).push_bytes(covenant_script.as_bytes()).into_script(),
+        CovenantType::P2wsh => tx.inputs[0].witness = vec![sig, covenant_script.into_bytes()],
+        CovenantType::P2tr => {
+            let control_block = build_taproot_leaf(&covenant_script).1;
+            tx.inputs[0].witness = vec![sig, covenant_script.into_bytes(), control_block];
+        }
+    }
+    tx.txid = parser::tx_to_txid(tx);
+}
+
+// Spends the bump output of a covenant tx into an op_return with the spacechain hash
+pub fn build_feebump_tx(txid: &Txid, vout: u32, amount: Amount, output_hash: &[u8], covenant_type: CovenantType) -> transaction::Content {
+    let (mut inputs, mut outputs) = (vec![], vec![]);
+    // satisfy the p2sh/p2wsh/p2tr by revealing the lock script
+    let (scriptsig, witness) = match covenant_type {
+        CovenantType::P2sh => (parser::Builder::new().push_bytes(build_bump_script().as_bytes()).into_script(), vec![]),
+        CovenantType::P2wsh => (Script::default(), vec![build_bump_script().into_bytes()]),
+        CovenantType::P2tr => (Script::default(), vec![build_bump_script().into_bytes(), build_taproot_leaf(&build_bump_script()).1])
+    };
+    
+    inputs.push(transaction::Input {
+        txid:       *txid,
+        vout,
+        scriptsig,
+        sequence:   Sequence(0), // script forces this to 0
+        witness
+    });
+
+    outputs.push(transaction::Output {
+        value:         amount, // - 104 - 104,
+        scriptpubkey:  parser::Builder::new().push_opcode(parser::Opcode::OP_RETURN).push_bytes(output_hash).into_script() // Note tx may be too small without data (non-standard)
+    });
+    
+    let mut tx = transaction::Content {
+        txid:       Txid::default(), // calculated after tx is complete
+        version:    2, // needs to be >1 for op_csv
+        locktime:   LockTime::ZERO,
+        inputs,
+        outputs
+    };
+
+    tx.txid = parser::tx_to_txid(&tx);
+
+    return tx
+}
@@ -1,31 +1,141 @@
-use secp256k1::{SecretKey, PublicKey, Secp256k1, Message};
-use secp256k1::All;
+use secp256k1::{SecretKey, PublicKey, Message, KeyPair, XOnlyPublicKey, ecdsa, schnorr};
+use secp256k1::SECP256K1; // shared context, creating one per key or call takes milliseconds
+use super::parser::{self, ParseError};
 
 //use secp256k1::hashes::sha256; // Not sure how to double hash with this one, so using something else
 use sha2::{Sha256, Digest};
 
+// A key pair, or just a public key when only verifying (signing then returns an error)
 pub struct ECC {
-    curve: Secp256k1<All>, sk: SecretKey, pk: PublicKey
+    sk: Option<SecretKey>, pk: PublicKey, keypair: Option<KeyPair>
 }
 
 impl ECC {
     pub fn new(sk_string: &str) -> ECC {
-        let curve = Secp256k1::new();
         let sk_vec = hex::decode(sk_string).unwrap();
         let sk = SecretKey::from_slice(&sk_vec).unwrap();
-        let pk = PublicKey::from_secret_key(&curve, &sk);
-        return ECC { curve, sk, pk }
+        let pk = PublicKey::from_secret_key(SECP256K1, &sk);
+        let keypair = KeyPair::from_secret_key(SECP256K1, sk);
+        return ECC { sk: Some(sk), pk, keypair: Some(keypair) }
+    }
+
+    // Public key only (compressed or uncompressed hex), e.g. to check a presigned chain without its secret key
+    pub fn from_pk_string(pk_string: &str) -> Result<ECC, ParseError> {
+        return ECC::from_pk_bytes(&parser::hex_to_bytes(pk_string, "public key")?)
+    }
+
+    pub fn from_pk_bytes(pk_bytes: &[u8]) -> Result<ECC, ParseError> {
+        let pk = PublicKey::from_slice(pk_bytes).map_err(|_| ParseError::new(0, "public key", "isn't a point on the curve"))?;
+        return Ok(ECC { sk: None, pk, keypair: None })
+    }
+
+    fn no_secret_key() -> ParseError {
+        return ParseError::new(0, "secret key", "is needed to sign, this key only has its public key")
     }
 
     pub fn get_pk_string(&self) -> String {
         return hex::encode(self.pk.serialize())
     }
 
-    pub fn sign_ecdsa_der(&self, hex_string: &str) -> String {
-        let data = Sha256::digest(Sha256::digest(hex::decode(hex_string).unwrap()));
-        let message = Message::from_slice(&data).unwrap();
-        let sig = self.curve.sign_ecdsa(&message, &self.sk);
-        let sig_string = hex::encode(sig.serialize_der());
-        return sig_string
+    pub fn get_pk_bytes(&self) -> [u8; 33] {
+        return self.pk.serialize()
+    }
+
+    // Double hashes the data and signs it
+    pub fn sign_ecdsa_der(&self, data: &[u8]) -> Result<Vec<u8>, ParseError> {
+        return self.sign_ecdsa_hash_der(&Sha256::digest(Sha256::digest(data)).into())
+    }
+
+    // Signs an already calculated sighash
+    pub fn sign_ecdsa_hash_der(&self, hash: &[u8; 32]) -> Result<Vec<u8>, ParseError> {
+        let message = Message::from_slice(hash).unwrap();
+        let sig = SECP256K1.sign_ecdsa(&message, self.sk.as_ref().ok_or_else(ECC::no_secret_key)?);
+        return Ok(sig.serialize_der().to_vec())
+    }
+
+    // The BIP340 x-only public key, as used in tapscript
+    pub fn get_xonly_pk_bytes(&self) -> [u8; 32] {
+        return XOnlyPublicKey::from(self.pk).serialize()
+    }
+
+    // Signs a taproot sighash, without aux randomness so presigned chains are reproducible
+    pub fn sign_schnorr_hash(&self, hash: &[u8; 32]) -> Result<[u8; 64], ParseError> {
+        let message = Message::from_slice(hash).unwrap();
+        let sig = SECP256K1.sign_schnorr_no_aux_rand(&message, self.keypair.as_ref().ok_or_else(ECC::no_secret_key)?);
+        let mut sig_bytes = [0; 64];
+        sig_bytes.copy_from_slice(sig.as_ref());
+        return Ok(sig_bytes)
+    }
+
+    // Checks a parsed (strict DER, low S) signature against an already calculated sighash
+    pub fn verify_ecdsa_hash(&self, sig: &Signature, hash: &[u8; 32]) -> bool {
+        return SECP256K1.verify_ecdsa(&Message::from_slice(hash).unwrap(), &sig.sig, &self.pk).is_ok()
+    }
+
+    // Checks a BIP340 signature against the x-only public key and a taproot sighash
+    pub fn verify_schnorr_hash(&self, sig: &[u8; 64], hash: &[u8; 32]) -> bool {
+        return verify_schnorr_hash(&self.get_xonly_pk_bytes(), sig, hash)
     }
-}
\ No newline at end of file
+}
+
+// An ECDSA signature as pushed in scriptsigs and witnesses: DER followed by the sighash type
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signature {
+    sig: ecdsa::Signature, pub sighash_type: u8
+}
+
+impl Signature {
+    // Only accepts strict DER (BIP66) with a low S value (BIP62), which is what Core relays
+    pub fn from_bytes(bytes: &[u8]) -> Result<Signature, ParseError> {
+        if !parser::is_valid_signature_encoding(bytes) { return Err(ParseError::new(0, "signature", "isn't strict DER followed by a sighash type")) }
+        let (der, sighash_type) = bytes.split_at(bytes.len()-1);
+        let sig = ecdsa::Signature::from_der(der).map_err(|_| ParseError::new(0, "signature", "r or s is out of range"))?;
+        let mut low_s = sig;
+        low_s.normalize_s();
+        if low_s != sig { return Err(ParseError::new(bytes.len()-1, "signature", "has a high S value")) }
+        return Ok(Signature { sig, sighash_type: sighash_type[0] })
+    }
+
+    pub fn from_hex(sig_hex_string: &str) -> Result<Signature, ParseError> {
+        return Signature::from_bytes(&parser::hex_to_bytes(sig_hex_string, "signature")?)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.sig.serialize_der().to_vec();
+        bytes.push(self.sighash_type);
+        return bytes
+    }
+}
+
+// Tweaks a taproot internal key with the merkle root of its script tree
+// Returns the output key and whether its y coordinate is odd (needed for the control block)
+pub fn get_taproot_output_key(internal_key: &[u8; 32], merkle_root: &[u8; 32]) -> ([u8; 32], bool) {
+    return get_checked_taproot_output_key(internal_key, merkle_root).unwrap()
+}
+
+// Same, but None if the internal key isn't on the curve (as can happen with a control block from a witness)
+pub fn get_checked_taproot_output_key(internal_key: &[u8; 32], merkle_root: &[u8; 32]) -> Option<([u8; 32], bool)> {
+    let mut key = XOnlyPublicKey::from_slice(internal_key).ok()?;
+    let parity = key.tweak_add_assign(SECP256K1, &parser::get_taptweak(internal_key, merkle_root)).ok()?;
+    return Some((key.serialize(), i32::from(parity) == 1))
+}
+
+// Checks a DER signature (without sighash byte) against a sighash
+// High S values are accepted like consensus does, strict DER encoding has to be checked by the caller
+pub fn verify_ecdsa_hash(pubkey: &[u8], der_sig: &[u8], hash: &[u8; 32]) -> bool {
+    let (pk, mut sig) = match (PublicKey::from_slice(pubkey), ecdsa::Signature::from_der_lax(der_sig)) {
+        (Ok(pk), Ok(sig)) => (pk, sig),
+        _ => return false
+    };
+    sig.normalize_s(); // libsecp256k1 only verifies low S signatures
+    return SECP256K1.verify_ecdsa(&Message::from_slice(hash).unwrap(), &sig, &pk).is_ok()
+}
+
+// Checks a BIP340 signature against a taproot sighash
+pub fn verify_schnorr_hash(xonly_pubkey: &[u8; 32], sig: &[u8; 64], hash: &[u8; 32]) -> bool {
+    let (pk, sig) = match (XOnlyPublicKey::from_slice(xonly_pubkey), schnorr::Signature::from_slice(sig)) {
+        (Ok(pk), Ok(sig)) => (pk, sig),
+        _ => return false
+    };
+    return SECP256K1.verify_schnorr(&sig, &Message::from_slice(hash).unwrap(), &pk).is_ok()
+}
@@ -0,0 +1,686 @@
+use sha2::{Sha256, Digest};
+use ripemd::{Ripemd160};
+use secp256k1::hashes::{sha1, Hash};
+use std::fmt;
+use super::transaction::{self, Sequence, LockTime};
+use super::parser::{self, Script, Opcode, Instruction, Bytestream, Builder};
+use super::ecc;
+
+// Consensus limits on scripts and their stacks
+const MAX_SCRIPT_SIZE: usize = 10_000;
+const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+const MAX_OPS_PER_SCRIPT: usize = 201;
+const MAX_STACK_SIZE: usize = 1000;
+const MAX_PUBKEYS_PER_MULTISIG: i64 = 20;
+// Tapscript replaces the op limit with a signature budget that grows with the witness size
+const VALIDATION_WEIGHT_PER_SIGOP: i64 = 50;
+const VALIDATION_WEIGHT_OFFSET: i64 = 50;
+const TAPROOT_CONTROL_BASE_SIZE: usize = 33;
+const TAPROOT_CONTROL_MAX_NODES: usize = 128;
+const ANNEX_TAG: u8 = 0x50;
+
+// Why an input doesn't validate: the input, the script that failed and the offset of the failing opcode in it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptError {
+    pub input_index: usize, pub script: String, pub offset: usize, pub reason: String
+}
+
+impl ScriptError {
+    pub fn new(input_index: usize, script: &str, offset: usize, reason: &str) -> ScriptError {
+        return ScriptError { input_index, script: script.to_string(), offset, reason: reason.to_string() }
+    }
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "script error in input {} at byte {} of the {}: {}", self.input_index, self.offset, self.script, self.reason)
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+// Rules differ between legacy scripts (including p2sh), segwit v0 and tapscript
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SigVersion {
+    Base, WitnessV0, Tapscript
+}
+
+// Everything the opcodes need to know about the input being verified
+struct Checker<'a> {
+    tx: &'a transaction::Content, input_index: usize, prevouts: &'a [Option<transaction::Output>],
+    script_name: &'static str, sig_version: SigVersion,
+    leaf_hash: [u8; 32], validation_weight_left: i64 // tapscript only
+}
+
+impl<'a> Checker<'a> {
+    fn prevout(&self) -> &transaction::Output {
+        return self.prevouts[self.input_index].as_ref().unwrap() // checked by verify_input
+    }
+
+    // Checks a legacy or segwit v0 signature, an empty signature is allowed to fail (e.g. for OP_NOTIF)
+    fn check_ecdsa(&self, sig: &[u8], pubkey: &[u8], script_code: &[u8]) -> Result<bool, &'static str> {
+        if sig.is_empty() { return Ok(false) }
+        if !parser::is_valid_signature_encoding(sig) { return Err("signature isn't strict DER (BIP66)") }
+        let sighash_type = sig[sig.len()-1] as u32;
+        let sighash = match self.sig_version {
+            SigVersion::Base => parser::get_legacy_sighash(self.tx, self.input_index as u64, script_code, sighash_type),
+            _ => parser::get_segwit_v0_sighash(self.tx, self.input_index as u64, script_code, self.prevout().value.to_sat(), sighash_type)
+        };
+        return Ok(ecc::verify_ecdsa_hash(pubkey, &sig[..sig.len()-1], &sighash))
+    }
+
+    // Checks a tapscript signature, where a present but invalid signature fails the whole script
+    fn check_schnorr(&mut self, sig: &[u8], pubkey: &[u8], codeseparator_position: u32) -> Result<bool, &'static str> {
+        if !sig.is_empty() {
+            self.validation_weight_left -= VALIDATION_WEIGHT_PER_SIGOP;
+            if self.validation_weight_left < 0 { return Err("tapscript exceeds its signature budget") }
+        }
+        if pubkey.is_empty() { return Err("empty public key") }
+        if pubkey.len() != 32 { return Ok(!sig.is_empty()) } // unknown public key types are left for future soft forks
+        if sig.is_empty() { return Ok(false) }
+        self.verify_schnorr(pubkey.try_into().unwrap(), sig, Some(codeseparator_position))?;
+        return Ok(true)
+    }
+
+    // Verifies a BIP340 signature for a key path spend (None) or a tapscript
+    fn verify_schnorr(&self, pubkey: &[u8; 32], sig: &[u8], codeseparator_position: Option<u32>) -> Result<(), &'static str> {
+        let sighash_type = match sig.len() {
+            64 => parser::SIGHASH_DEFAULT,
+            65 if sig[64] != 0 => sig[64] as u32, // SIGHASH_DEFAULT has to be implicit
+            _ => return Err("invalid schnorr signature size")
+        };
+        let prevouts: Option<Vec<transaction::Output>> = self.prevouts.iter().cloned().collect();
+        let prevouts = prevouts.ok_or("taproot signatures commit to every spent output, but some are unknown")?;
+        let index = self.input_index as u64;
+        let sighash = match codeseparator_position {
+            Some(position) => parser::get_tapscript_sighash(self.tx, index, &prevouts, &self.leaf_hash, position, sighash_type),
+            None => parser::get_taproot_sighash(self.tx, index, &prevouts, None, sighash_type)
+        }.ok_or("invalid sighash type")?;
+        if !ecc::verify_schnorr_hash(pubkey, sig[..64].try_into().unwrap(), &sighash) { return Err("invalid schnorr signature") }
+        return Ok(())
+    }
+
+    // OP_CHECKLOCKTIMEVERIFY: the tx locktime has to be of the same type and at least as late
+    fn check_locktime(&self, locktime: i64) -> bool {
+        let tx_locktime = self.tx.locktime.0 as i64;
+        let threshold = LockTime::THRESHOLD as i64;
+        if (tx_locktime < threshold) != (locktime < threshold) { return false }
+        if locktime > tx_locktime { return false }
+        // a final input would make the tx locktime meaningless
+        return self.tx.inputs[self.input_index].sequence != Sequence::MAX
+    }
+
+    // OP_CHECKSEQUENCEVERIFY: the input's BIP68 relative locktime has to be of the same type and at least as long
+    // (that enough blocks or time passed since the spent output confirmed is up to the node)
+    fn check_sequence(&self, sequence: i64) -> bool {
+        let tx_sequence = self.tx.inputs[self.input_index].sequence.0 as i64;
+        if self.tx.version < 2 { return false } // BIP68 only applies from version 2
+        if tx_sequence & Sequence::LOCKTIME_DISABLE_FLAG as i64 != 0 { return false }
+        let mask = (Sequence::LOCKTIME_TYPE_FLAG | Sequence::LOCKTIME_MASK) as i64;
+        let type_flag = Sequence::LOCKTIME_TYPE_FLAG as i64;
+        let (tx_sequence, sequence) = (tx_sequence & mask, sequence & mask);
+        if (tx_sequence < type_flag) != (sequence < type_flag) { return false }
+        return sequence <= tx_sequence
+    }
+}
+
+// Verifies the scripts of every input, prevouts are the outputs spent by each input (in input order)
+pub fn verify_tx(tx: &transaction::Content, prevouts: &[transaction::Output]) -> Result<(), ScriptError> {
+    if tx.is_coinbase() { return Ok(()) }
+    let prevouts: Vec<Option<transaction::Output>> = prevouts.iter().cloned().map(Some).collect();
+    for input_index in 0..tx.inputs.len() {
+        verify_input(tx, input_index, &prevouts)?;
+    }
+    return Ok(())
+}
+
+// Verifies the scripts of one input with every soft fork up to taproot active
+// prevouts has an entry for every input, only the spent output of this input is required (taproot needs all of them)
+pub fn verify_input(tx: &transaction::Content, input_index: usize, prevouts: &[Option<transaction::Output>]) -> Result<(), ScriptError> {
+    let error = |script: &str, reason: &str| ScriptError::new(input_index, script, 0, reason);
+    if input_index >= tx.inputs.len() { return Err(error("input", "doesn't exist")) }
+    if prevouts.len() != tx.inputs.len() { return Err(error("input", "needs a (possibly unknown) spent output for every input")) }
+    let prevout = prevouts[input_index].as_ref().ok_or_else(|| error("input", "the spent output is unknown"))?;
+    let input = &tx.inputs[input_index];
+    let mut checker = Checker {
+        tx, input_index, prevouts, script_name: "scriptsig", sig_version: SigVersion::Base, leaf_hash: [0; 32], validation_weight_left: 0
+    };
+
+    let mut stack = vec![];
+    eval_script(&mut stack, &input.scriptsig, &mut checker)?;
+    let p2sh_stack = stack.clone();
+    checker.script_name = "scriptpubkey";
+    eval_script(&mut stack, &prevout.scriptpubkey, &mut checker)?;
+    if !stack.last().is_some_and(|top| cast_to_bool(top)) {
+        return Err(ScriptError::new(input_index, "scriptpubkey", prevout.scriptpubkey.len(), "script evaluated to false"))
+    }
+
+    let mut has_witness_program = false;
+    if let Some((version, program)) = witness_program(prevout.scriptpubkey.as_bytes()) {
+        has_witness_program = true;
+        if !input.scriptsig.is_empty() { return Err(error("scriptsig", "has to be empty when spending a witness program")) }
+        verify_witness_program(&mut checker, version, program, &input.witness, false)?;
+    }
+
+    if is_p2sh(prevout.scriptpubkey.as_bytes()) {
+        if !is_push_only(&input.scriptsig) { return Err(error("scriptsig", "can only push data when spending p2sh")) }
+        stack = p2sh_stack;
+        let redeem_script = Script::new(stack.pop().ok_or_else(|| error("scriptsig", "doesn't push a redeem script"))?);
+        checker.script_name = "redeem script";
+        checker.sig_version = SigVersion::Base;
+        eval_script(&mut stack, &redeem_script, &mut checker)?;
+        if !stack.last().is_some_and(|top| cast_to_bool(top)) {
+            return Err(ScriptError::new(input_index, "redeem script", redeem_script.len(), "script evaluated to false"))
+        }
+        if let Some((version, program)) = witness_program(redeem_script.as_bytes()) {
+            has_witness_program = true;
+            if input.scriptsig.as_bytes() != serialized_push(redeem_script.as_bytes()) {
+                return Err(error("scriptsig", "can only push the redeem script when spending p2sh wrapped segwit"))
+            }
+            verify_witness_program(&mut checker, version, program, &input.witness, true)?;
+        }
+    }
+
+    if !has_witness_program && !input.witness.is_empty() { return Err(error("witness", "isn't allowed without a witness program")) }
+    return Ok(())
+}
+
+fn verify_witness_program(checker: &mut Checker, version: u8, program: &[u8], witness: &[Vec<u8>], is_p2sh: bool) -> Result<(), ScriptError> {
+    let input_index = checker.input_index;
+    let error = |reason: &str| ScriptError::new(input_index, "witness", 0, reason);
+    if version == 0 {
+        checker.sig_version = SigVersion::WitnessV0;
+        if program.len() == 32 { // p2wsh: the last witness item is the script
+            let (script, stack) = witness.split_last().ok_or_else(|| error("is empty"))?;
+            if Sha256::digest(script)[..] != program[..] { return Err(error("witness script doesn't match the program")) }
+            checker.script_name = "witness script";
+            return execute_witness_script(checker, stack.to_vec(), &Script::new(script.clone()))
+        }
+        if program.len() == 20 { // p2wpkh: executed as the equivalent p2pkh script
+            if witness.len() != 2 { return Err(error("p2wpkh needs exactly a signature and a public key")) }
+            let script = Builder::new().push_opcode(Opcode::OP_DUP).push_opcode(Opcode::OP_HASH160).push_bytes(program)
+                .push_opcode(Opcode::OP_EQUALVERIFY).push_opcode(Opcode::OP_CHECKSIG).into_script();
+            checker.script_name = "p2wpkh script";
+            return execute_witness_script(checker, witness.to_vec(), &script)
+        }
+        return Err(error("witness v0 programs are 20 or 32 bytes"))
+    }
+    if version != 1 || program.len() != 32 || is_p2sh { return Ok(()) } // left for future soft forks
+
+    let output_key: [u8; 32] = program.try_into().unwrap();
+    if witness.is_empty() { return Err(error("is empty")) }
+    if witness.len() >= 2 && witness[witness.len()-1].first() == Some(&ANNEX_TAG) { return Err(error("annexes aren't supported")) }
+    if witness.len() == 1 { // key path
+        return checker.verify_schnorr(&output_key, &witness[0], None).map_err(error)
+    }
+
+    // script path: the control block proves the script is a leaf of the output key's tree
+    let (control, rest) = witness.split_last().unwrap();
+    let (script, stack) = rest.split_last().unwrap();
+    if control.len() < TAPROOT_CONTROL_BASE_SIZE || control.len() > TAPROOT_CONTROL_BASE_SIZE + 32*TAPROOT_CONTROL_MAX_NODES
+        || !(control.len() - TAPROOT_CONTROL_BASE_SIZE).is_multiple_of(32) {
+        return Err(error("invalid control block size"))
+    }
+    let leaf_version = control[0] & 0xfe;
+    let leaf_hash = parser::get_tapleaf_hash_for_version(leaf_version, script);
+    let mut node = leaf_hash;
+    for sibling in control[TAPROOT_CONTROL_BASE_SIZE..].chunks(32) {
+        // branches hash their children in lexicographic order
+        let (left, right) = if node[..] < sibling[..] { (&node[..], sibling) } else { (sibling, &node[..]) };
+        node = parser::tagged_hash("TapBranch", &[left, right].concat());
+    }
+    match ecc::get_checked_taproot_output_key(control[1..33].try_into().unwrap(), &node) {
+        Some((key, odd)) if key == output_key && odd == (control[0] & 1 == 1) => {}
+        _ => return Err(error("control block doesn't commit to the script"))
+    }
+    if leaf_version != parser::TAPROOT_LEAF_VERSION { return Ok(()) } // left for future soft forks
+
+    checker.sig_version = SigVersion::Tapscript;
+    checker.script_name = "tapscript";
+    checker.leaf_hash = leaf_hash;
+    let witness_size = parser::compact_size::encoded_len(witness.len() as u64)
+        + witness.iter().map(|item| parser::compact_size::encoded_len(item.len() as u64) + item.len()).sum::<usize>();
+    checker.validation_weight_left = VALIDATION_WEIGHT_OFFSET + witness_size as i64;
+    return execute_witness_script(checker, stack.to_vec(), &Script::new(script.clone()))
+}
+
+// Runs a segwit script, which has to leave exactly one true item on the stack
+fn execute_witness_script(checker: &mut Checker, mut stack: Vec<Vec<u8>>, script: &Script) -> Result<(), ScriptError> {
+    let (input_index, script_name) = (checker.input_index, checker.script_name);
+    let error = |offset: usize, reason: &str| ScriptError::new(input_index, script_name, offset, reason);
+    if checker.sig_version == SigVersion::Tapscript {
+        // any OP_SUCCESSx makes the script succeed right away (they're reserved for future soft forks)
+        let mut stream = Bytestream::new(script.as_bytes());
+        while stream.remaining() > 0 {
+            let offset = stream.offset();
+            match Script::next_instruction(&mut stream) {
+                Ok(Instruction::Op(opcode)) if is_op_success(opcode as u8) => return Ok(()),
+                Ok(Instruction::Unknown(byte)) if is_op_success(byte) => return Ok(()),
+                Ok(_) => {}
+                Err(e) => return Err(error(offset, &format!("{} runs past the end of the script", e.field)))
+            }
+        }
+        if stack.len() > MAX_STACK_SIZE { return Err(error(0, "witness has more than 1000 stack items")) }
+    }
+    if stack.iter().any(|item| item.len() > MAX_SCRIPT_ELEMENT_SIZE) { return Err(error(0, "witness item is larger than 520 bytes")) }
+    eval_script(&mut stack, script, checker)?;
+    if stack.len() != 1 { return Err(error(script.len(), &format!("script left {} stack items instead of 1", stack.len()))) }
+    if !cast_to_bool(&stack[0]) { return Err(error(script.len(), "script evaluated to false")) }
+    return Ok(())
+}
+
+// Executes a script on the stack, like Core's EvalScript
+fn eval_script(stack: &mut Vec<Vec<u8>>, script: &Script, checker: &mut Checker) -> Result<(), ScriptError> {
+    let (input_index, script_name, sig_version) = (checker.input_index, checker.script_name, checker.sig_version);
+    let tapscript = sig_version == SigVersion::Tapscript;
+    let bytes = script.as_bytes();
+    if !tapscript && bytes.len() > MAX_SCRIPT_SIZE { return Err(ScriptError::new(input_index, script_name, 0, "script is larger than 10000 bytes")) }
+
+    let mut stream = Bytestream::new(bytes);
+    let mut alt_stack: Vec<Vec<u8>> = vec![];
+    let mut conditions: Vec<bool> = vec![]; // one per open OP_IF, false while skipping its branch
+    let mut op_count = 0;
+    let mut script_code_start = 0; // legacy and segwit v0 sign the script after the last OP_CODESEPARATOR
+    let mut codeseparator_position = u32::MAX; // tapscript signs its opcode index instead
+    let mut opcode_position = 0_u32;
+    while stream.remaining() > 0 {
+        let offset = stream.offset();
+        let fail = |reason: &str| ScriptError::new(input_index, script_name, offset, reason);
+        let need = |stack: &Vec<Vec<u8>>, items: usize| {
+            if stack.len() < items { Err(fail(&format!("needs {} stack items but there are {}", items, stack.len()))) } else { Ok(()) }
+        };
+        let number = |data: &[u8], max_size: usize| script_num(data, max_size).ok_or_else(|| fail("number is out of range"));
+        let instruction = Script::next_instruction(&mut stream).map_err(|e| fail(&format!("{} runs past the end of the script", e.field)))?;
+        let executing = conditions.iter().all(|condition| *condition);
+
+        let opcode = match instruction {
+            Instruction::Push(data) => {
+                if data.len() > MAX_SCRIPT_ELEMENT_SIZE { return Err(fail("push is larger than 520 bytes")) }
+                if executing { stack.push(data) }
+                if stack.len() + alt_stack.len() > MAX_STACK_SIZE { return Err(fail("stack has more than 1000 items")) }
+                opcode_position += 1;
+                continue
+            }
+            Instruction::Unknown(byte) => {
+                if !tapscript { op_count += 1 }
+                if op_count > MAX_OPS_PER_SCRIPT { return Err(fail("script has more than 201 opcodes")) }
+                if executing { return Err(fail(&format!("unknown opcode 0x{:02x}", byte))) }
+                opcode_position += 1;
+                continue
+            }
+            Instruction::Op(opcode) => opcode
+        };
+        let byte = opcode as u8;
+        if !tapscript && byte > Opcode::OP_16 as u8 { op_count += 1 }
+        if op_count > MAX_OPS_PER_SCRIPT { return Err(fail("script has more than 201 opcodes")) }
+        if is_disabled(opcode) { return Err(fail(&format!("{} is disabled", opcode.name()))) } // even when not executed
+        // conditionals are tracked while skipping a branch, everything else only runs when executing
+        let is_conditional = (Opcode::OP_IF as u8..=Opcode::OP_ENDIF as u8).contains(&byte);
+        if !executing && !is_conditional {
+            opcode_position += 1;
+            continue
+        }
+
+        if opcode == Opcode::OP_1NEGATE || (Opcode::OP_1 as u8..=Opcode::OP_16 as u8).contains(&byte) {
+            stack.push(parser::encode_script_num(byte as i64 - 0x50));
+        }
+        else { match opcode {
+            Opcode::OP_NOP | Opcode::OP_NOP1 | Opcode::OP_NOP4 | Opcode::OP_NOP5 | Opcode::OP_NOP6 | Opcode::OP_NOP7
+                | Opcode::OP_NOP8 | Opcode::OP_NOP9 | Opcode::OP_NOP10 => {}
+            Opcode::OP_CHECKLOCKTIMEVERIFY => {
+                need(stack, 1)?;
+                let locktime = number(&stack[stack.len()-1], 5)?; // 5 bytes, as locktimes go up to 2^32-1
+                if locktime < 0 { return Err(fail("negative locktime")) }
+                if !checker.check_locktime(locktime) { return Err(fail("locktime requirement not satisfied")) }
+            }
+            Opcode::OP_CHECKSEQUENCEVERIFY => {
+                need(stack, 1)?;
+                let sequence = number(&stack[stack.len()-1], 5)?;
+                if sequence < 0 { return Err(fail("negative sequence")) }
+                // with the disable flag set it's a nop, leaving room for future relative locktime types
+                if sequence & Sequence::LOCKTIME_DISABLE_FLAG as i64 == 0 && !checker.check_sequence(sequence) {
+                    return Err(fail("relative locktime requirement not satisfied"))
+                }
+            }
+            Opcode::OP_IF | Opcode::OP_NOTIF => {
+                let mut condition = false;
+                if executing {
+                    need(stack, 1)?;
+                    let top = stack.pop().unwrap();
+                    // MINIMALIF is consensus in tapscript
+                    if tapscript && (top.len() > 1 || (top.len() == 1 && top[0] != 1)) { return Err(fail("OP_IF argument has to be empty or 0x01 in tapscript")) }
+                    condition = cast_to_bool(&top) == (opcode == Opcode::OP_IF);
+                }
+                conditions.push(condition);
+            }
+            Opcode::OP_ELSE => {
+                let last = conditions.last_mut().ok_or_else(|| fail("OP_ELSE without OP_IF"))?;
+                *last = !*last;
+            }
+            Opcode::OP_ENDIF => {
+                conditions.pop().ok_or_else(|| fail("OP_ENDIF without OP_IF"))?;
+            }
+            Opcode::OP_VERIFY => {
+                need(stack, 1)?;
+                if !cast_to_bool(&stack.pop().unwrap()) { return Err(fail("OP_VERIFY failed")) }
+            }
+            Opcode::OP_RETURN => return Err(fail("OP_RETURN")),
+
+            // stack
+            Opcode::OP_TOALTSTACK => {
+                need(stack, 1)?;
+                alt_stack.push(stack.pop().unwrap());
+            }
+            Opcode::OP_FROMALTSTACK => stack.push(alt_stack.pop().ok_or_else(|| fail("alt stack is empty"))?),
+            Opcode::OP_2DROP => {
+                need(stack, 2)?;
+                stack.truncate(stack.len()-2);
+            }
+            Opcode::OP_2DUP | Opcode::OP_3DUP => {
+                let count = if opcode == Opcode::OP_2DUP { 2 } else { 3 };
+                need(stack, count)?;
+                stack.extend_from_within(stack.len()-count..);
+            }
+            Opcode::OP_2OVER => {
+                need(stack, 4)?;
+                stack.extend_from_within(stack.len()-4..stack.len()-2);
+            }
+            Opcode::OP_2ROT => {
+                need(stack, 6)?;
+                let start = stack.len()-6;
+                let items: Vec<Vec<u8>> = stack.drain(start..start+2).collect();
+                stack.extend(items);
+            }
+            Opcode::OP_2SWAP => {
+                need(stack, 4)?;
+                let len = stack.len();
+                stack.swap(len-4, len-2);
+                stack.swap(len-3, len-1);
+            }
+            Opcode::OP_IFDUP => {
+                need(stack, 1)?;
+                if cast_to_bool(&stack[stack.len()-1]) { stack.extend_from_within(stack.len()-1..) }
+            }
+            Opcode::OP_DEPTH => stack.push(parser::encode_script_num(stack.len() as i64)),
+            Opcode::OP_DROP => {
+                need(stack, 1)?;
+                stack.pop();
+            }
+            Opcode::OP_DUP => {
+                need(stack, 1)?;
+                stack.extend_from_within(stack.len()-1..);
+            }
+            Opcode::OP_NIP => {
+                need(stack, 2)?;
+                stack.remove(stack.len()-2);
+            }
+            Opcode::OP_OVER => {
+                need(stack, 2)?;
+                stack.extend_from_within(stack.len()-2..stack.len()-1);
+            }
+            Opcode::OP_PICK | Opcode::OP_ROLL => {
+                need(stack, 2)?;
+                let depth = number(&stack.pop().unwrap(), 4)?;
+                if depth < 0 || depth >= stack.len() as i64 { return Err(fail(&format!("{} depth {} is out of range", opcode.name(), depth))) }
+                let index = stack.len() - 1 - depth as usize;
+                let item = if opcode == Opcode::OP_ROLL { stack.remove(index) } else { stack[index].clone() };
+                stack.push(item);
+            }
+            Opcode::OP_ROT => {
+                need(stack, 3)?;
+                let item = stack.remove(stack.len()-3);
+                stack.push(item);
+            }
+            Opcode::OP_SWAP => {
+                need(stack, 2)?;
+                let len = stack.len();
+                stack.swap(len-2, len-1);
+            }
+            Opcode::OP_TUCK => {
+                need(stack, 2)?;
+                let top = stack[stack.len()-1].clone();
+                stack.insert(stack.len()-2, top);
+            }
+            Opcode::OP_SIZE => {
+                need(stack, 1)?;
+                stack.push(parser::encode_script_num(stack[stack.len()-1].len() as i64));
+            }
+
+            // comparison
+            Opcode::OP_EQUAL | Opcode::OP_EQUALVERIFY => {
+                need(stack, 2)?;
+                let equal = stack.pop() == stack.pop();
+                if opcode == Opcode::OP_EQUALVERIFY {
+                    if !equal { return Err(fail("OP_EQUALVERIFY failed")) }
+                }
+                else { stack.push(from_bool(equal)) }
+            }
+
+            // arithmetic on numbers of up to 4 bytes (results may be longer)
+            Opcode::OP_1ADD | Opcode::OP_1SUB | Opcode::OP_NEGATE | Opcode::OP_ABS | Opcode::OP_NOT | Opcode::OP_0NOTEQUAL => {
+                need(stack, 1)?;
+                let a = number(&stack.pop().unwrap(), 4)?;
+                let result = match opcode {
+                    Opcode::OP_1ADD => a + 1,
+                    Opcode::OP_1SUB => a - 1,
+                    Opcode::OP_NEGATE => -a,
+                    Opcode::OP_ABS => a.abs(),
+                    Opcode::OP_NOT => (a == 0) as i64,
+                    _ => (a != 0) as i64
+                };
+                stack.push(parser::encode_script_num(result));
+            }
+            Opcode::OP_ADD | Opcode::OP_SUB | Opcode::OP_BOOLAND | Opcode::OP_BOOLOR | Opcode::OP_NUMEQUAL | Opcode::OP_NUMEQUALVERIFY
+                | Opcode::OP_NUMNOTEQUAL | Opcode::OP_LESSTHAN | Opcode::OP_GREATERTHAN | Opcode::OP_LESSTHANOREQUAL
+                | Opcode::OP_GREATERTHANOREQUAL | Opcode::OP_MIN | Opcode::OP_MAX => {
+                need(stack, 2)?;
+                let b = number(&stack.pop().unwrap(), 4)?;
+                let a = number(&stack.pop().unwrap(), 4)?;
+                let result = match opcode {
+                    Opcode::OP_ADD => a + b,
+                    Opcode::OP_SUB => a - b,
+                    Opcode::OP_BOOLAND => (a != 0 && b != 0) as i64,
+                    Opcode::OP_BOOLOR => (a != 0 || b != 0) as i64,
+                    Opcode::OP_NUMEQUAL | Opcode::OP_NUMEQUALVERIFY => (a == b) as i64,
+                    Opcode::OP_NUMNOTEQUAL => (a != b) as i64,
+                    Opcode::OP_LESSTHAN => (a < b) as i64,
+                    Opcode::OP_GREATERTHAN => (a > b) as i64,
+                    Opcode::OP_LESSTHANOREQUAL => (a <= b) as i64,
+                    Opcode::OP_GREATERTHANOREQUAL => (a >= b) as i64,
+                    Opcode::OP_MIN => a.min(b),
+                    _ => a.max(b)
+                };
+                if opcode == Opcode::OP_NUMEQUALVERIFY {
+                    if result == 0 { return Err(fail("OP_NUMEQUALVERIFY failed")) }
+                }
+                else { stack.push(parser::encode_script_num(result)) }
+            }
+            Opcode::OP_WITHIN => {
+                need(stack, 3)?;
+                let max = number(&stack.pop().unwrap(), 4)?;
+                let min = number(&stack.pop().unwrap(), 4)?;
+                let x = number(&stack.pop().unwrap(), 4)?;
+                stack.push(from_bool(min <= x && x < max));
+            }
+
+            // crypto
+            Opcode::OP_RIPEMD160 | Opcode::OP_SHA1 | Opcode::OP_SHA256 | Opcode::OP_HASH160 | Opcode::OP_HASH256 => {
+                need(stack, 1)?;
+                let data = stack.pop().unwrap();
+                stack.push(match opcode {
+                    Opcode::OP_RIPEMD160 => Ripemd160::digest(&data).to_vec(),
+                    Opcode::OP_SHA1 => sha1::Hash::hash(&data).into_inner().to_vec(),
+                    Opcode::OP_SHA256 => Sha256::digest(&data).to_vec(),
+                    Opcode::OP_HASH160 => Ripemd160::digest(Sha256::digest(&data)).to_vec(),
+                    _ => parser::hash256(&data).to_vec()
+                });
+            }
+            Opcode::OP_CODESEPARATOR => {
+                script_code_start = stream.offset();
+                codeseparator_position = opcode_position;
+            }
+            Opcode::OP_CHECKSIG | Opcode::OP_CHECKSIGVERIFY => {
+                need(stack, 2)?;
+                let pubkey = stack.pop().unwrap();
+                let sig = stack.pop().unwrap();
+                let success = if tapscript { checker.check_schnorr(&sig, &pubkey, codeseparator_position) }
+                else { checker.check_ecdsa(&sig, &pubkey, &script_code(&bytes[script_code_start..], &[&sig], sig_version)) }.map_err(fail)?;
+                if opcode == Opcode::OP_CHECKSIGVERIFY {
+                    if !success { return Err(fail("OP_CHECKSIGVERIFY failed")) }
+                }
+                else { stack.push(from_bool(success)) }
+            }
+            Opcode::OP_CHECKSIGADD if tapscript => {
+                need(stack, 3)?;
+                let pubkey = stack.pop().unwrap();
+                let n = number(&stack.pop().unwrap(), 4)?;
+                let sig = stack.pop().unwrap();
+                let success = checker.check_schnorr(&sig, &pubkey, codeseparator_position).map_err(fail)?;
+                stack.push(parser::encode_script_num(n + success as i64));
+            }
+            Opcode::OP_CHECKMULTISIG | Opcode::OP_CHECKMULTISIGVERIFY => {
+                if tapscript { return Err(fail("OP_CHECKMULTISIG isn't available in tapscript, use OP_CHECKSIGADD")) }
+                // stack: dummy <sigs> sig_count <pubkeys> key_count
+                need(stack, 1)?;
+                let mut key_count = number(&stack[stack.len()-1], 4)?;
+                if !(0..=MAX_PUBKEYS_PER_MULTISIG).contains(&key_count) { return Err(fail("invalid public key count")) }
+                op_count += key_count as usize;
+                if op_count > MAX_OPS_PER_SCRIPT { return Err(fail("script has more than 201 opcodes")) }
+                let mut key_depth = 2;
+                let mut depth = key_depth + key_count as usize;
+                need(stack, depth)?;
+                let mut sig_count = number(&stack[stack.len()-depth], 4)?;
+                if !(0..=key_count).contains(&sig_count) { return Err(fail("invalid signature count")) }
+                let mut sig_depth = depth + 1;
+                depth = sig_depth + sig_count as usize;
+                need(stack, depth)?;
+                let sigs: Vec<&[u8]> = (0..sig_count as usize).map(|i| &stack[stack.len()-sig_depth-i][..]).collect();
+                let script_code = script_code(&bytes[script_code_start..], &sigs, sig_version);
+
+                // signatures have to be in the same order as their public keys
+                let mut success = true;
+                while success && sig_count > 0 {
+                    let sig = &stack[stack.len()-sig_depth];
+                    let pubkey = &stack[stack.len()-key_depth];
+                    if checker.check_ecdsa(sig, pubkey, &script_code).map_err(fail)? {
+                        sig_depth += 1;
+                        sig_count -= 1;
+                    }
+                    key_depth += 1;
+                    key_count -= 1;
+                    if sig_count > key_count { success = false }
+                }
+                stack.truncate(stack.len()+1-depth);
+                // an extra item is popped because of an off by one in the original implementation
+                need(stack, 1)?;
+                if !stack.pop().unwrap().is_empty() { return Err(fail("OP_CHECKMULTISIG dummy has to be empty (BIP147)")) }
+                if opcode == Opcode::OP_CHECKMULTISIGVERIFY {
+                    if !success { return Err(fail("OP_CHECKMULTISIGVERIFY failed")) }
+                }
+                else { stack.push(from_bool(success)) }
+            }
+            _ => return Err(fail(&format!("invalid opcode {}", opcode.name())))
+        }}
+
+        if stack.len() + alt_stack.len() > MAX_STACK_SIZE { return Err(fail("stack has more than 1000 items")) }
+        opcode_position += 1;
+    }
+    if !conditions.is_empty() { return Err(ScriptError::new(input_index, script_name, bytes.len(), "OP_IF without OP_ENDIF")) }
+    return Ok(())
+}
+
+// Legacy signatures can't sign themselves, so they're removed from the signed script code (Core's FindAndDelete)
+fn script_code(script: &[u8], sigs: &[&[u8]], sig_version: SigVersion) -> Vec<u8> {
+    let mut script_code = script.to_vec();
+    if sig_version == SigVersion::Base {
+        for sig in sigs { script_code = find_and_delete(&script_code, &serialized_push(sig)) }
+    }
+    return script_code
+}
+
+// Removes every occurrence of pattern that starts at an instruction boundary
+fn find_and_delete(script: &[u8], pattern: &[u8]) -> Vec<u8> {
+    let mut result = vec![];
+    let (mut start, mut index) = (0, 0);
+    loop {
+        result.extend_from_slice(&script[start..index]);
+        while script.len() - index >= pattern.len() && script[index..].starts_with(pattern) { index += pattern.len() }
+        start = index;
+        if index >= script.len() { break }
+        let mut stream = Bytestream::new(&script[index..]);
+        if Script::next_instruction(&mut stream).is_err() { break } // the rest is kept as it is
+        index += stream.offset();
+    }
+    result.extend_from_slice(&script[start..]);
+    return result
+}
+
+// Pushes data the way Core's CScript << vector does (unlike Builder::push_bytes it never uses OP_1-OP_16)
+fn serialized_push(data: &[u8]) -> Vec<u8> {
+    let mut bytes = match data.len() {
+        0..=0x4b => vec![data.len() as u8],
+        0x4c..=0xff => vec![Opcode::OP_PUSHDATA1 as u8, data.len() as u8],
+        0x100..=0xffff => [&[Opcode::OP_PUSHDATA2 as u8][..], &(data.len() as u16).to_le_bytes()].concat(),
+        _ => [&[Opcode::OP_PUSHDATA4 as u8][..], &(data.len() as u32).to_le_bytes()].concat()
+    };
+    bytes.extend_from_slice(data);
+    return bytes
+}
+
+// A number on the stack, None if it's longer than max_size bytes
+fn script_num(data: &[u8], max_size: usize) -> Option<i64> {
+    if data.len() > max_size { return None }
+    return Some(parser::decode_script_num(data))
+}
+
+// Anything but (negative) zero is true
+fn cast_to_bool(data: &[u8]) -> bool {
+    return match data.iter().rposition(|byte| *byte != 0) {
+        Some(i) => !(i == data.len()-1 && data[i] == 0x80),
+        None => false
+    }
+}
+
+fn from_bool(value: bool) -> Vec<u8> {
+    return if value { vec![1] } else { vec![] }
+}
+
+// Opcodes that fail the script even in an unexecuted branch (the disabled ones are OP_SUCCESSx in tapscript)
+fn is_disabled(opcode: Opcode) -> bool {
+    return matches!(opcode, Opcode::OP_CAT | Opcode::OP_SUBSTR | Opcode::OP_LEFT | Opcode::OP_RIGHT | Opcode::OP_INVERT
+        | Opcode::OP_AND | Opcode::OP_OR | Opcode::OP_XOR | Opcode::OP_2MUL | Opcode::OP_2DIV | Opcode::OP_MUL | Opcode::OP_DIV
+        | Opcode::OP_MOD | Opcode::OP_LSHIFT | Opcode::OP_RSHIFT | Opcode::OP_VERIF | Opcode::OP_VERNOTIF)
+}
+
+// BIP342 OP_SUCCESSx opcodes
+fn is_op_success(byte: u8) -> bool {
+    return matches!(byte, 80 | 98 | 126..=129 | 131..=134 | 137..=138 | 141..=142 | 149..=153 | 187..=254)
+}
+
+// OP_HASH160 <20 bytes> OP_EQUAL
+fn is_p2sh(scriptpubkey: &[u8]) -> bool {
+    return scriptpubkey.len() == 23 && scriptpubkey[0] == Opcode::OP_HASH160 as u8 && scriptpubkey[1] == 20
+        && scriptpubkey[22] == Opcode::OP_EQUAL as u8
+}
+
+// A version (OP_0 to OP_16) followed by a single push of 2 to 40 bytes
+fn witness_program(script: &[u8]) -> Option<(u8, &[u8])> {
+    if script.len() < 4 || script.len() > 42 || script[1] as usize != script.len()-2 { return None }
+    return match script[0] {
+        0x00 => Some((0, &script[2..])),
+        byte if (Opcode::OP_1 as u8..=Opcode::OP_16 as u8).contains(&byte) => Some((byte - 0x50, &script[2..])),
+        _ => None
+    }
+}
+
+// Only pushes (OP_1NEGATE to OP_16 included), as required for p2sh scriptsigs
+fn is_push_only(script: &Script) -> bool {
+    return match script.instructions() {
+        Ok(instructions) => instructions.iter().all(|instruction| match instruction {
+            Instruction::Push(_) => true,
+            Instruction::Op(opcode) => *opcode as u8 <= Opcode::OP_16 as u8,
+            Instruction::Unknown(_) => false
+        }),
+        Err(_) => false
+    }
+}
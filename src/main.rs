@@ -1,208 +1,306 @@
-#![allow(dead_code,unused_variables)]
+#![allow(dead_code,unused_variables,clippy::needless_return,clippy::upper_case_acronyms,clippy::needless_range_loop)]
 
 mod parser;
 mod core_rpc;
+#[cfg(test)]
 mod tests;
 mod transaction;
+mod block;
+mod network;
+mod address;
+mod psbt;
 mod ecc;
+mod interpreter;
+mod audit;
+mod chain_file;
+mod compact_chain;
+mod chain_index;
+mod chain_spec;
+mod covenant;
 
 fn main() {
-
-    tests::run();
-
-    // Gets command line parameters (txid hash rawtransaction)
+    // Gets command line parameters (txid hash rawtransaction [input_satoshis])
     let args: Vec<String> = std::env::args().collect();
-    if args.len() == 4 {
-        // TODO: parameter checks (valid length, valid hex)
+    if (3..=5).contains(&args.len()) && args[1] == "generatechain" {
+        // Signs a chain ahead of time and writes it for publishing, the only mode that needs the secret key
+        let spec = match args.get(4) {
+            Some(path) => match chain_spec::ChainSpec::read(path) {
+                Ok(spec) => spec,
+                Err(e) => { println!("Invalid parameter, can't read {}: {}", path, e); return }
+            },
+            None => chain_spec::ChainSpec::signet()
+        };
+        let reps = match args.get(3).map(|a| a.parse::<u64>()) {
+            Some(Ok(reps)) => reps,
+            Some(Err(_)) => { println!("Invalid parameter, reps must be a whole number"); return }
+            None => match spec.covenant() {
+                Ok(covenant) => spec.affordable_steps(covenant.vsize),
+                Err(e) => { println!("Invalid parameter, {}", e); return }
+            }
+        };
+        match chain_file::generate_chain_file(&args[2], &spec, reps) {
+            Ok(chain) => match chain.write(&spec.chain_file_path()) {
+                Ok(()) => println!("Wrote {} covenant txs to {}", chain.txs.len(), spec.chain_file_path()),
+                Err(e) => println!("Can't write {}: {}", spec.chain_file_path(), e)
+            },
+            Err(e) => println!("Invalid parameter, {}", e)
+        }
+     }
+     else if args.len() == 2 && args[1] == "chainspec" {
+        // A spec to start from for another chain (change its name, network, key, genesis and amounts)
+        println!("{}", chain_spec::ChainSpec::signet().to_json());
+     }
+     else if args.len() == 4 && args[1] == "audit" {
+        // Audits a published chain file against the covenant pubkey it's supposed to be signed by
+        let chain = match chain_file::ChainFile::read(&args[3]) {
+            Ok(chain) => chain,
+            Err(e) => { println!("Invalid parameter, can't read {}: {}", args[3], e); return }
+        };
+        if chain.spec.pubkey != args[2] { println!("Audit failed, the chain file is for covenant pubkey {}", chain.spec.pubkey); return }
+        match audit::audit_covenant_tx_sequence(&chain.spec, &chain.tx_strings()) {
+            Ok(report) => println!("{}", report),
+            Err(violation) => println!("Audit failed, {}", violation)
+        }
+     }
+     else if args.len() == 4 && args[1] == "compactchain" {
+        // Keeps only the signatures of a chain file, the txs are rebuilt from the header by expandchain
+        match chain_file::ChainFile::read(&args[2]) {
+            Ok(chain) => {
+                let compact = compact_chain::encode(&chain);
+                match std::fs::write(&args[3], &compact) {
+                    Ok(()) => println!("Wrote {} covenant signatures to {} ({} bytes)", chain.txs.len(), args[3], compact.len()),
+                    Err(e) => println!("Can't write {}: {}", args[3], e)
+                }
+            }
+            Err(e) => println!("Invalid parameter, can't read {}: {}", args[2], e)
+        }
+     }
+     else if args.len() == 4 && args[1] == "expandchain" {
+        // Rebuilds a chain file from its signatures, every one of them is checked against the covenant pubkey
+        match std::fs::read(&args[2]).map_err(|e| e.into()).and_then(|bytes| compact_chain::reconstruct(&bytes)) {
+            Ok(chain) => match chain.write(&args[3]) {
+                Ok(()) => println!("Rebuilt and verified {} covenant txs signed by {}, wrote {}", chain.txs.len(), chain.spec.pubkey, args[3]),
+                Err(e) => println!("Can't write {}: {}", args[3], e)
+            },
+            Err(e) => println!("Invalid parameter, can't rebuild {}: {}", args[2], e)
+        }
+     }
+     else if (args.len() == 3 || args.len() == 4) && args[1] == "decoderawtransaction" {
+        // Addresses are shown for the signet chain's network unless another one is given
+        let network = match args.get(3) {
+            Some(name) => match network::Network::from_name(name) {
+                Some(network) => network,
+                None => { println!("Invalid parameter, unknown network {} (main, test, signet or regtest)", name); return }
+            },
+            None => chain_spec::ChainSpec::signet().network
+        };
+        match parser::decode_from_hex(&args[2]) {
+            Ok(tx) => println!("{}", core_rpc::tx_to_json(&tx, &network)),
+            Err(e) => println!("Invalid parameter, {}", e)
+        }
+     }
+     else if args.len() == 4 || args.len() == 5 {
+        let cpfp_input_satoshis = match args.get(4).map(|a| a.parse::<u64>().ok().and_then(transaction::Amount::from_sat)) {
+            Some(Some(satoshis)) => Some(satoshis),
+            Some(None) => { println!("Invalid parameter, input_satoshis must be a whole number of sats (at most 21 million btc)"); return }
+            None => None
+        };
+        let chains = read_chain_files();
+        if chains.is_empty() { println!("No covenant chain (*_chain.json) in this directory, generate one with: generatechain secret_key [reps] [spec_file]"); return }
         println!("Generating...");
-        let (cov_tx, cpfp_tx) = generate_next_cov_tx_and_cpfp(&args[1], &args[2], &args[3]);
-        println!("Covenant tx:\n{}", cov_tx);
-        println!("Fee-bumping cpfp tx:\n{}", cpfp_tx);
-        println!("DONE!");
+        match generate_next_cov_tx_and_cpfp(&chains, &args[1], &args[2], &args[3], cpfp_input_satoshis) {
+            Ok((cov_tx, cpfp_tx, cpfp_psbt)) => {
+                println!("Covenant tx:\n{}", cov_tx);
+                println!("Fee-bumping cpfp tx:\n{}", cpfp_tx);
+                println!("Fee-bumping cpfp psbt:\n{}", cpfp_psbt);
+                println!("DONE!");
+            }
+            Err(e) => println!("Invalid parameter, {}", e)
+        }
+     }
+     else if args.len() == 3 && args[1] == "chainstep" {
+        // Shows where a covenant txid is in the chains of this directory and how many txs are left after it
+        let txid = match transaction::Txid::from_hex(&args[2]) {
+            Ok(txid) => txid,
+            Err(e) => { println!("Invalid parameter, {}", e); return }
+        };
+        let mut found = false;
+        for chain in &read_chain_files() {
+            let index = chain.index();
+            if txid == chain.spec.genesis_txid {
+                println!("{} is the genesis tx of the {} chain, {} covenant txs follow it", txid, chain.spec.name, index.len());
+                found = true;
+            }
+            if let (Some(step), Some(remaining)) = (index.step(&txid), index.remaining(&txid)) {
+                println!("{} is covenant tx {} of {} in the {} chain (spends {} sats), {} covenant txs remaining", txid, step, index.len(), chain.spec.name, index.input_value(step).unwrap(), remaining);
+                found = true;
+            }
+        }
+        if !found { println!("{} isn't in any covenant chain (*_chain.json) in this directory", txid) }
+     }
+     else if args.len() == 3 && args[1] == "decodescript" {
+        match parser::Script::from_hex(&args[2]) {
+            Ok(script) => println!("{}", script.to_asm()),
+            Err(e) => println!("Invalid parameter, {}", e)
+        }
      }
      else {
         println!("Please run this with the following parameters in hex: covenant_txid spacechain_hash cpfp_rawtransaction");
+        println!("The cpfp_rawtransaction can also be a base64 psbt");
+        println!("Optionally add the amount of sats in the cpfp_rawtransaction input to see its fee rate");
+        println!("Or disassemble a script with: decodescript script_hex");
+        println!("Or find where a covenant txid is in the chains of this directory with: chainstep txid");
+        println!("Or show a transaction as Core's json with: decoderawtransaction rawtransaction [network] (signet addresses without a network)");
+        println!("Or audit a published covenant chain with: audit covenant_pubkey chain_file");
+        println!("Or shrink a chain file to its signatures with: compactchain chain_file compact_file");
+        println!("Or rebuild and verify a chain file from its signatures with: expandchain compact_file chain_file");
+        println!("Or sign a covenant chain into <name>_chain.json with: generatechain secret_key [reps] [spec_file] (the signet chain without a spec_file)");
+        println!("Or print the signet chain spec (to write a spec_file from) with: chainspec");
     }
 }
 
-// Main function, outputs the cov_tx and cpfp_tx with user added hash and fee
-fn generate_next_cov_tx_and_cpfp(prev_txid: &str, hash: &str, rawtransaction: &str) -> (String, String) {
-    
-    // Find the next covenant tx (based on the txid of the previous one)
-    const KEY_STRING: &str = "eb445ec7e0fd814db1e84622cddad9cd30154ee22bc6c2a4a61f6287be39f2d2";
-    const INPUT_TXID: &str = "60c31751818bd4410eed84b1c9047863206cce2c7d4d610ce5841c4195ba6c3b"; // signet now, was regtest "2715afb15d8f92028de0fd98e68e48ee496ac83f5c9dbf031a0a6a21a7e9cd59";
-    const INPUT_VOUT: u64 = 1; // Note this isn't a fixed value
-    const INPUT_SATOSHIS: u64 = 100_000; // signet now, was 10*100_000_000;
-    let (cov_tx_string, cov_txid) = find_covenant_tx(KEY_STRING, INPUT_TXID.to_string(), INPUT_VOUT, INPUT_SATOSHIS, prev_txid);
+// Main function, outputs the cov_tx and cpfp_tx (as hex and as a base64 psbt) with user added hash and fee (and prints their fee rates)
+fn generate_next_cov_tx_and_cpfp(chains: &[chain_file::ChainFile], prev_txid: &str, hash: &str, rawtransaction: &str, cpfp_input_satoshis: Option<transaction::Amount>) -> Result<(String, String, String), Box<dyn std::error::Error>> {
+    let prev_txid = transaction::Txid::from_hex(prev_txid)?;
+    let hash = parser::hex_to_bytes(hash, "spacechain hash")?;
+    if hash.len() > MAX_OP_RETURN_DATA { return Err(Box::new(parser::ParseError::new(MAX_OP_RETURN_DATA, "spacechain hash", "more than 80 bytes doesn't fit in a standard op_return"))) }
+    // A psbt keeps whatever the user's wallet knows about its input (base64 psbts start with "cHNidP8")
+    let mut cpfp_psbt = if rawtransaction.starts_with("cHNidP8") {
+        psbt::decode_from_base64(rawtransaction)?
+    } else {
+        psbt::Psbt::from_tx(&parser::decode_from_hex(rawtransaction)?)
+    };
+
+    // Find the next covenant tx (based on the txid of the previous one) in whichever chain has it
+    let (cov_tx, cov_input_satoshis, covenant_type) = find_covenant_tx(chains, &prev_txid)
+        .ok_or_else(|| parser::ParseError::new(0, "covenant txid", "has no successor in the chain files"))?;
 
     // Build the cpfp input and op_return output
-    let cpfp_tx = build_feebump_tx(&cov_txid, 1, 800, hash);
+    let cpfp_tx = covenant::build_feebump_tx(&cov_tx.txid, 1, cov_tx.outputs[1].value, &hash, covenant_type);
 
     // Take the rawtransaction (assumed 1 input 1 output) and merge it with the above
-    let mut raw_tx = parser::decode_from_hex(rawtransaction);
-    raw_tx.inputs.push(cpfp_tx.inputs[0].clone());
-    raw_tx.outputs.push(cpfp_tx.outputs[0].clone());
+    // The bump input needs no signature, so it's finalized right away and wallets only sign their own input
+    let bump_input = cpfp_tx.inputs[0].clone();
+    cpfp_psbt.tx.inputs.push(transaction::Input { scriptsig: parser::Script::default(), ..bump_input });
+    cpfp_psbt.inputs.push(match covenant_type {
+        covenant::CovenantType::P2sh => psbt::Input {
+            non_witness_utxo:  Some(cov_tx.clone()),
+            redeem_script:     Some(covenant::build_bump_script()),
+            final_scriptsig:   Some(cpfp_tx.inputs[0].scriptsig.clone()),
+            ..Default::default()
+        },
+        // segwit spends reveal the bump script (and for p2tr its control block) in the witness instead
+        covenant::CovenantType::P2wsh | covenant::CovenantType::P2tr => psbt::Input {
+            witness_utxo:         Some(cov_tx.outputs[1].clone()),
+            witness_script:       if covenant_type == covenant::CovenantType::P2wsh { Some(covenant::build_bump_script()) } else { None },
+            final_scriptwitness:  Some(cpfp_tx.inputs[0].witness.clone()),
+            ..Default::default()
+        }
+    });
+    cpfp_psbt.tx.outputs.push(cpfp_tx.outputs[0].clone());
+    cpfp_psbt.outputs.push(psbt::Output::default());
+    cpfp_psbt.tx.txid = parser::tx_to_txid(&cpfp_psbt.tx);
+    let raw_tx = cpfp_psbt.extract_tx();
     let merged_raw_tx_string = parser::encode_to_hex(&raw_tx);
 
-    return (cov_tx_string, merged_raw_tx_string)
-}
+    // Check the covenant and bump spends before they get anywhere near bitcoind (the user's input isn't signed yet)
+    let cov_prevout = transaction::Output { value: cov_input_satoshis, scriptpubkey: cov_tx.outputs[0].scriptpubkey.clone() };
+    interpreter::verify_input(&cov_tx, 0, &[Some(cov_prevout)])?;
+    let bump_index = raw_tx.inputs.len() - 1;
+    let mut prevouts: Vec<Option<transaction::Output>> = (0..bump_index).map(|i| cpfp_psbt.input_utxo(i)).collect();
+    prevouts.push(Some(cov_tx.outputs[1].clone()));
+    interpreter::verify_input(&raw_tx, bump_index, &prevouts)?;
 
-// Changes the transaction object and outputs a signed hex tx
-fn sign_tx(tx: &mut transaction::Content, input_index: u64, input_scriptpubkey: &str, key: &ecc::ECC) -> String {
-    let sh_all = parser::get_sighash_all_data(&tx, input_index, input_scriptpubkey);
-    let sig = key.sign_ecdsa_der(&sh_all) + "01"; // 01 == sighash_all
-    tx.inputs[input_index as usize].scriptsig = parser::get_length_prefixed_string(&sig); // TODO: doesn't work if more than sig is needed
-    tx.txid = parser::tx_to_txid(&tx);
-    let tx_string = parser::encode_to_hex(&tx);
-    return tx_string
+    let cpfp_input_satoshis = cpfp_input_satoshis.or_else(|| cpfp_psbt.input_value(0));
+    print_fee_rates(&cov_tx, cov_input_satoshis, &raw_tx, cpfp_input_satoshis);
+
+    return Ok((parser::encode_to_hex(&cov_tx), merged_raw_tx_string, psbt::encode_to_base64(&cpfp_psbt)?))
 }
 
-// Builds a partial transaction that can pay for the cpfp (not needed if rawtransaction is used instead)
-fn build_paying_tx(input_txid: &str, input_vout: u64, input_amount: u64, output_scriptpubkey: &str, fee: u64) -> transaction::Content {
-    let (mut inputs, mut outputs) = (vec![], vec![]);
-    
-    inputs.push(transaction::Input {
-        txid:       input_txid.to_string(),
-        vout:       input_vout,
-        scriptsig:  String::new(), // still needs a sig later
-        sequence:   0
-    });
+// Standard op_return outputs are at most 83 bytes: OP_RETURN OP_PUSHDATA1 <size> and the data
+const MAX_OP_RETURN_DATA: usize = 80;
 
-    outputs.push(transaction::Output {
-        value:         input_amount - fee,
-        scriptpubkey:  output_scriptpubkey.to_string()
+// Shows what the covenant tx and the merged cpfp tx pay, separately and as a package
+fn print_fee_rates(cov_tx: &transaction::Content, cov_input_satoshis: transaction::Amount, cpfp_tx: &transaction::Content, cpfp_input_satoshis: Option<transaction::Amount>) {
+    let cov_fee = cov_tx.fee(&[cov_input_satoshis]).unwrap().to_sat();
+    let cov_vsize = parser::get_vsize(cov_tx);
+    println!("Covenant tx: {} vB, fee {} sats ({:.2} sat/vB)", cov_vsize, cov_fee, cov_fee as f64 / cov_vsize as f64);
+    let cpfp_vsize = parser::get_vsize(cpfp_tx);
+    // The bump input is the last one, its value is the covenant's bump output
+    let cpfp_fee = cpfp_input_satoshis.and_then(|satoshis| {
+        if cpfp_tx.inputs.len() != 2 { return None } // only the assumed 1 input rawtransaction is supported
+        cpfp_tx.fee(&[satoshis, cov_tx.outputs[1].value])
     });
-    
-    let mut tx = transaction::Content {
-        txid:       String::new(), // calculated after tx is complete
-        version:    2, // needs to be >1 for op_csv
-        locktime:   0,
-        inputs,
-        outputs
-    };
-
-    tx.txid = parser::tx_to_txid(&tx);
+    match cpfp_fee {
+        Some(cpfp_fee) => {
+            let cpfp_fee = cpfp_fee.to_sat();
+            println!("Fee-bumping cpfp tx: {} vB before signing, fee {} sats ({:.2} sat/vB)", cpfp_vsize, cpfp_fee, cpfp_fee as f64 / cpfp_vsize as f64);
+            println!("Combined: {:.2} sat/vB (signatures will lower this a little)", (cov_fee + cpfp_fee) as f64 / (cov_vsize + cpfp_vsize) as f64);
+        }
+        None => println!("Fee-bumping cpfp tx: {} vB before signing (fee unknown, input_satoshis missing or not matching)", cpfp_vsize)
+    }
+}
 
-    return tx
+// Changes the transaction object and outputs a signed hex tx (sighash_type is e.g. parser::SIGHASH_ALL)
+fn sign_tx(tx: &mut transaction::Content, input_index: u64, input_scriptpubkey: &[u8], key: &ecc::ECC, sighash_type: u32) -> Result<String, parser::ParseError> {
+    let sighash = parser::get_legacy_sighash(tx, input_index, input_scriptpubkey, sighash_type);
+    let mut sig = key.sign_ecdsa_hash_der(&sighash)?;
+    sig.push(sighash_type as u8); // the signature ends with the sighash flag
+    tx.inputs[input_index as usize].scriptsig = parser::Builder::new().push_bytes(&sig).into_script(); // TODO: doesn't work if more than sig is needed
+    tx.txid = parser::tx_to_txid(tx);
+    let tx_string = parser::encode_to_hex(tx);
+    return Ok(tx_string)
 }
 
-fn build_feebump_tx(txid: &str, vout: u64, amount: u64, output_hex_hash: &str) -> transaction::Content {
+// Builds a partial transaction that can pay for the cpfp (not needed if rawtransaction is used instead)
+fn build_paying_tx(input_txid: &transaction::Txid, input_vout: u32, input_amount: transaction::Amount, output_address: &str, network: &network::Network, fee: transaction::Amount) -> Result<transaction::Content, parser::ParseError> {
     let (mut inputs, mut outputs) = (vec![], vec![]);
     
     inputs.push(transaction::Input {
-        txid:       txid.to_string(),
-        vout:       vout,
-        scriptsig:  parser::get_length_prefixed_string(&build_bump_script()), // satisfy the p2sh by revealing the lock script
-        sequence:   0 // script forces this to 0
+        txid:       *input_txid,
+        vout:       input_vout,
+        scriptsig:  parser::Script::default(), // still needs a sig later
+        sequence:   transaction::Sequence(0),
+        witness:    vec![]
     });
 
     outputs.push(transaction::Output {
-        value:         amount, // - 104 - 104,
-        scriptpubkey: "6a".to_string() + &parser::get_length_prefixed_string(&output_hex_hash) // Note tx may be too small without data (non-standard)
+        value:         input_amount.checked_sub(fee).ok_or_else(|| parser::ParseError::new(0, "fee", "exceeds the input amount"))?,
+        scriptpubkey:  parser::Script::new(address::address_to_scriptpubkey(output_address, network)?)
     });
     
     let mut tx = transaction::Content {
-        txid:       String::new(), // calculated after tx is complete
+        txid:       transaction::Txid::default(), // calculated after tx is complete
         version:    2, // needs to be >1 for op_csv
-        locktime:   0,
+        locktime:   transaction::LockTime::ZERO,
         inputs,
         outputs
     };
 
     tx.txid = parser::tx_to_txid(&tx);
 
-    return tx
+    return Ok(tx)
 }
 
-// Generates the covenant transactions (needs to be pre-calculated and published instead of key)
-fn generate_covenant_tx_sequence(key_string: &str, mut input_txid: String, mut input_vout: u64, mut input_satoshis: u64, reps: u64) -> Vec<String> {
-    const COST: u64 = 2000;
-    if input_satoshis < COST*reps { panic!("Insufficient funds to generate the desired number of transactions. Requires {} sats", COST*reps); }
-    let mut covenant_tx_sequence = vec![];
-    let key = ecc::ECC::new(key_string);
-    //let script = build_covenant_script(&key.get_pk_string());
-    //let p2sh_address = parser::script_to_p2sh_address(&script);
-    //println!("{}", p2sh_address); // 2NEcniP26o4oF2jUHgTAcncJnKt653gxrLw
-    for _ in 0..reps {
-        let tx = build_covenant_tx(&input_txid, input_vout, input_satoshis, &key);
-        let tx_string = parser::encode_to_hex(&tx);
-        covenant_tx_sequence.push(tx_string);
-        input_txid = tx.txid.clone();
-        input_vout = 0;
-        input_satoshis -= COST;
-    }
-    return covenant_tx_sequence
+
+// Gets the next transaction for use in the covenant from whichever published chain has it, as well as the value of the output it spends
+fn find_covenant_tx(chains: &[chain_file::ChainFile], target_txid: &transaction::Txid) -> Option<(transaction::Content, transaction::Amount, covenant::CovenantType)> {
+    return chains.iter().find_map(|chain| chain.index().successor(target_txid).map(|(tx, input_satoshis)| (tx.clone(), input_satoshis, chain.spec.covenant_type)))
 }
 
-// Gets the next transaction for use in the covenant, as well as its txid
-fn find_covenant_tx(key_string: &str, mut input_txid: String, mut input_vout: u64, mut input_satoshis: u64, target_txid: &str) -> (String, String) {
-    const COST: u64 = 2000;
-    let key = ecc::ECC::new(key_string);
-    //let script = build_covenant_script(&key.get_pk_string());
-    //let p2sh_address = parser::script_to_p2sh_address(&script);
-    //println!("{}", p2sh_address); // 2NEcniP26o4oF2jUHgTAcncJnKt653gxrLw
-    let mut bool = false;
-    for _ in 0..52560 { // year
-        let tx = build_covenant_tx(&input_txid, input_vout, input_satoshis, &key);
-        let tx_string = parser::encode_to_hex(&tx);
-        if bool { return (tx_string, tx.txid.clone()) };
-        input_txid = tx.txid.clone();
-        if input_txid == target_txid { bool = true };
-        input_vout = 0;
-        if input_satoshis < COST { panic!("Couldn't find target txid (or ran out of coins)") }
-        input_satoshis -= COST;
+// Every chain file (<name>_chain.json) in the current directory, so chains of different specs can be used side by side
+fn read_chain_files() -> Vec<chain_file::ChainFile> {
+    let mut paths: Vec<String> = std::fs::read_dir(".").map(|entries| entries
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|name| name.ends_with("_chain.json"))
+        .collect()).unwrap_or_default();
+    paths.sort();
+    let mut chains = vec![];
+    for path in paths {
+        match chain_file::ChainFile::read(&path) {
+            Ok(chain) => chains.push(chain),
+            Err(e) => println!("Skipping {}: {}", path, e)
+        }
     }
-    panic!("Couldn't find target txid")
+    return chains
 }
-
-// The main bmm script that's in use
-fn build_covenant_script(pubkey_hex_string: &str) -> String {
-    return parser::get_length_prefixed_string(&pubkey_hex_string) + "ad" + "51" + "b2"
-} // OP_PUSHBYTES_XX <Pubkey> OP_CHECKSIGVERIFY OP_PUSHNUM_1 OP_CSV
-
-fn build_bump_script() -> String {
-    return String::new() + "00" + "b2" + "8b"
-} // OP_0 OP_CSV OP_1ADD (forces RBF)
-
-// Generates the covenant tx (note, input and output are assumed to have the same script, even the 1st input)
-fn build_covenant_tx(input_txid: &str, input_vout: u64, input_satoshis: u64, key: &ecc::ECC) -> transaction::Content {
-    let (mut inputs, mut outputs) = (vec![], vec![]);
-    let (dust_limit, fee) = (800, 1200); // TODO: shave down these numbers (573 for p2sh dust?)
-    let covenant_script = build_covenant_script(&key.get_pk_string());
-    let p2sh_script = parser::script_to_p2sh_script(&covenant_script);
-    
-    inputs.push(transaction::Input {
-        txid:       input_txid.to_string(),
-        vout:       input_vout,
-        scriptsig:  String::new(), // will be sig + covenant_script
-        sequence:   1 // matches script relative locktime of 1 block
-    });
-
-    outputs.push(transaction::Output {
-        value:         input_satoshis - dust_limit - fee,
-        scriptpubkey:  p2sh_script.clone()
-    });
-
-    outputs.push(transaction::Output {
-        value:         dust_limit,
-        scriptpubkey:  parser::script_to_p2sh_script(&build_bump_script()) 
-    });
-
-    //let p2sh_address = parser::script_to_p2sh_address(&"00b28b");
-    //println!("{p2sh_address}"); // 2MzHTWrk6TpuPAauCaWcPNpEs4Q9VYW6iCQ
-
-    let mut tx = transaction::Content {
-        txid:       String::new(), // calculated after sig is obtained
-        version:    2, // needs to be >1 for op_csv
-        locktime:   0,
-        inputs,
-        outputs
-    };
-    // TODO: signing should not be done inside this function
-    let sighash_all_data = parser::get_sighash_all_data(&tx, 0, &covenant_script); // Note: NOT p2sh_script
-    let sig = key.sign_ecdsa_der(&sighash_all_data) + "01"; // sighash flag needs to be added
-    tx.inputs[0].scriptsig = parser::get_length_prefixed_string(&sig) + &parser::get_length_prefixed_string(&covenant_script);
-    tx.txid = parser::tx_to_txid(&tx);
-
-    return tx
-}
\ No newline at end of file
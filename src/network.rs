@@ -0,0 +1,71 @@
+// The chains the software can be pointed at, with everything that differs between them
+#[derive(Debug, Clone, PartialEq)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    Signet, // the default signet, which the covenant chain currently lives on
+    Regtest,
+    CustomSignet { challenge: Vec<u8> } // signet with its own block signing script (-signetchallenge)
+}
+
+impl Network {
+    // Parses the names used by Core's -chain option
+    pub fn from_name(name: &str) -> Option<Network> {
+        return match name {
+            "main" | "mainnet" => Some(Network::Mainnet),
+            "test" | "testnet" => Some(Network::Testnet),
+            "signet" => Some(Network::Signet),
+            "regtest" => Some(Network::Regtest),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Network::Mainnet => "main",
+            Network::Testnet => "test",
+            Network::Signet => "signet",
+            Network::Regtest => "regtest",
+            Network::CustomSignet { .. } => "custom signet"
+        }
+    }
+
+    // Base58 version byte of p2pkh addresses (1... on mainnet, m.../n... elsewhere)
+    pub fn p2pkh_prefix(&self) -> u8 {
+        return if *self == Network::Mainnet { 0x00 } else { 0x6f }
+    }
+
+    // Base58 version byte of p2sh addresses (3... on mainnet, 2... elsewhere)
+    pub fn p2sh_prefix(&self) -> u8 {
+        return if *self == Network::Mainnet { 0x05 } else { 0xc4 }
+    }
+
+    // Human readable part of segwit addresses
+    pub fn bech32_hrp(&self) -> &'static str {
+        return match self {
+            Network::Mainnet => "bc",
+            Network::Regtest => "bcrt",
+            _ => "tb"
+        }
+    }
+
+    // Default p2p port
+    pub fn default_port(&self) -> u16 {
+        return match self {
+            Network::Mainnet => 8333,
+            Network::Testnet => 18333,
+            Network::Signet | Network::CustomSignet { .. } => 38333,
+            Network::Regtest => 18444
+        }
+    }
+
+    // Default rpc port
+    pub fn default_rpc_port(&self) -> u16 {
+        return match self {
+            Network::Mainnet => 8332,
+            Network::Testnet => 18332,
+            Network::Signet | Network::CustomSignet { .. } => 38332,
+            Network::Regtest => 18443
+        }
+    }
+}
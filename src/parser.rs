@@ -1,142 +1,526 @@
-use hex;
 use sha2::{Sha256, Digest};
-use bs58;
 use ripemd::{Ripemd160};
 use super::transaction;
+use super::block;
+use super::network::Network;
 #[path = "./bytestream.rs"] mod bytestream;
+#[path = "./compact_size.rs"] pub mod compact_size;
+pub use bytestream::{ParseError, Bytestream, ByteWriter};
+#[path = "./script.rs"] mod script;
+pub use script::{Opcode, Instruction, Script, Builder, encode_script_num, decode_script_num, is_valid_signature_encoding};
 
-// NOTE: missing weight, script parsing
+// Double sha256, as used for txids and sighashes
+pub fn hash256(data: &[u8]) -> [u8; 32] {
+    return Sha256::digest(Sha256::digest(data)).into()
+}
 
 // Turns a lock script into a hash for use in a p2sh output
-pub fn script_to_p2sh_hash160(script_hex_string: &str) -> String {
-    let hash1 = Sha256::digest(hex::decode(script_hex_string).unwrap());
+pub fn script_to_p2sh_hash160(script: &[u8]) -> [u8; 20] {
+    let hash1 = Sha256::digest(script);
     let mut hasher = Ripemd160::new();
     hasher.update(hash1);
-    let hash2 = hasher.finalize();
-    return hex::encode(hash2).to_string()
+    return hasher.finalize().into()
 }
 
-// Turns a lock script into a valid p2sh address
-pub fn script_to_p2sh_address(script_hex_string: &str) -> String {
-    let address = "C4".to_string() + &script_to_p2sh_hash160(script_hex_string); // C4 = testnet, 05 = mainnet
-    let hash3 = Sha256::digest(Sha256::digest(hex::decode(&address).unwrap()));
-    let checksum = &hex::encode(hash3)[0..8];
-    let base58 = bs58::encode(hex::decode(address + checksum).unwrap()).into_string();
-    return base58
+// Turns a lock script into a valid p2sh address for the given network
+pub fn script_to_p2sh_address(script: &[u8], network: &Network) -> String {
+    return super::address::encode_base58check(network.p2sh_prefix(), &script_to_p2sh_hash160(script))
 }
 
 // Turns an unlock script into a p2sh unlock script
-pub fn script_to_p2sh_script(script_hex_string: &str) -> String {
-    return "a9".to_string() + "14" + &script_to_p2sh_hash160(script_hex_string) + "87";
+pub fn script_to_p2sh_script(script: &[u8]) -> Vec<u8> {
+    return [&[0xa9, 0x14][..], &script_to_p2sh_hash160(script), &[0x87]].concat()
 }  // OP_HASH160 OP_PUSHBYTES20 <Hash160> OP_EQUAL
 
-// Turns transaction hex into an object with readable fields
-pub fn decode_from_hex(transaction_hex_string: &str) -> transaction::Content {
-    let mut tx = bytestream::Bytestream::new(transaction_hex_string);
-    let hash = Sha256::digest(Sha256::digest(hex::decode(transaction_hex_string).unwrap()));
-    let txid = bytestream::Bytestream::convert_endian(&hex::encode(hash));
-    let version = bytestream::Bytestream::bytes_to_u64(&tx.get_bytes(4, true));
-    let no_of_inputs = tx.get_varint();
+// Turns a witness script into a p2wsh output script
+pub fn script_to_p2wsh_script(script: &[u8]) -> Vec<u8> {
+    return [&[0x00, 0x20][..], &Sha256::digest(script)].concat()
+}  // OP_0 OP_PUSHBYTES32 <Sha256>
+
+// Turns a taproot output key (x-only) into a p2tr output script
+pub fn script_to_p2tr_script(output_key: &[u8; 32]) -> Vec<u8> {
+    return [vec![0x51, 0x20], output_key.to_vec()].concat()
+}
+
+// Decodes user supplied hex, reporting the byte offset of a bad character
+pub fn hex_to_bytes(hex_string: &str, field: &str) -> Result<Vec<u8>, ParseError> {
+    return hex::decode(hex_string).map_err(|e| match e {
+        hex::FromHexError::InvalidHexCharacter { index, .. } => ParseError::new(index/2, field, "invalid hex character"),
+        _ => ParseError::new(hex_string.len()/2, field, &e.to_string())
+    })
+}
+
+// Turns a txid as displayed by Core into bytes in serialization order
+pub fn txid_from_hex(txid_hex_string: &str) -> Result<[u8; 32], ParseError> {
+    let bytes = hex_to_bytes(txid_hex_string, "txid")?;
+    if bytes.len() != 32 { return Err(ParseError::new(0, "txid", &format!("expected 32 bytes, got {}", bytes.len()))) }
+    let mut txid = [0; 32];
+    txid.copy_from_slice(&bytes);
+    txid.reverse();
+    return Ok(txid)
+}
+
+// Turns a txid in serialization order into the hex shown by Core
+pub fn txid_to_hex(txid: &[u8; 32]) -> String {
+    let mut display = *txid;
+    display.reverse();
+    return hex::encode(display)
+}
+
+// Turns transaction hex into an object with readable fields (legacy or BIP144 segwit serialization)
+pub fn decode_from_hex(transaction_hex_string: &str) -> Result<transaction::Content, ParseError> {
+    return decode(&hex_to_bytes(transaction_hex_string, "hex string")?)
+}
+
+// Turns a serialized transaction into an object with readable fields
+pub fn decode(transaction_bytes: &[u8]) -> Result<transaction::Content, ParseError> {
+    let mut stream = Bytestream::new(transaction_bytes);
+    let tx = decode_from_stream(&mut stream)?;
+    stream.expect_end("end of transaction")?;
+    return Ok(tx)
+}
+
+// Reads a single transaction, leaving whatever follows it in the stream (e.g. the next tx of a block)
+fn decode_from_stream(tx: &mut Bytestream) -> Result<transaction::Content, ParseError> {
+    let version = tx.get_int(4, "version")? as u32;
+    let mut no_of_inputs = tx.get_varint("input count")?;
+    let mut has_witness = false;
+    if no_of_inputs == 0 { // 0x00 marker, so this is a segwit tx and the flag must follow
+        let offset = tx.offset();
+        let flag = tx.get_int(1, "segwit flag")?;
+        if flag != 1 { return Err(ParseError::new(offset, "segwit flag", &format!("unknown flag {}", flag))) }
+        has_witness = true;
+        no_of_inputs = tx.get_varint("input count")?;
+    }
     let mut inputs = vec![];
     for _ in 0..no_of_inputs {
-        let txid = hex::encode(tx.get_bytes(32, true));
-        let vout = bytestream::Bytestream::bytes_to_u64(&tx.get_bytes(4, true));
-        let scriptsig_size = tx.get_varint();
-        let scriptsig = hex::encode(tx.get_bytes(scriptsig_size, false));
-        let sequence = bytestream::Bytestream::bytes_to_u64(&tx.get_bytes(4, true));
-        inputs.push(transaction::Input { txid, vout, scriptsig, sequence });
-    }
-    let no_of_outputs = tx.get_varint();
+        let txid = transaction::Txid::from_bytes(tx.get_hash("input txid")?);
+        let vout = tx.get_int(4, "input vout")? as u32;
+        let scriptsig = Script::new(tx.get_length_prefixed("scriptsig")?.to_vec());
+        let sequence = transaction::Sequence(tx.get_int(4, "input sequence")? as u32);
+        inputs.push(transaction::Input { txid, vout, scriptsig, sequence, witness: vec![] });
+    }
+    let no_of_outputs = tx.get_varint("output count")?;
     let mut outputs = vec![];
     for _ in 0..no_of_outputs {
-        let value = bytestream::Bytestream::bytes_to_u64(&tx.get_bytes(8, true));
-        let scriptpubkey_size = tx.get_varint();
-        let scriptpubkey = hex::encode(tx.get_bytes(scriptpubkey_size, false));
+        let offset = tx.offset();
+        let value = transaction::Amount::from_sat(tx.get_int(8, "output value")?).ok_or_else(|| ParseError::new(offset, "output value", "exceeds MAX_MONEY"))?;
+        let scriptpubkey = Script::new(tx.get_length_prefixed("scriptpubkey")?.to_vec());
         outputs.push(transaction::Output { value, scriptpubkey });
     }
-    let locktime = bytestream::Bytestream::bytes_to_u64(&tx.get_bytes(4, true));
-    return transaction::Content { txid, version, locktime, inputs, outputs }
+    if has_witness { // one witness stack per input
+        let offset = tx.offset();
+        for input in inputs.iter_mut() {
+            let no_of_items = tx.get_varint("witness item count")?;
+            for _ in 0..no_of_items {
+                input.witness.push(tx.get_length_prefixed("witness item")?.to_vec());
+            }
+        }
+        if inputs.iter().all(|i| i.witness.is_empty()) { return Err(ParseError::new(offset, "witness", "superfluous witness record")) }
+    }
+    let locktime = transaction::LockTime(tx.get_int(4, "locktime")? as u32);
+    let mut tx = transaction::Content { txid: transaction::Txid::default(), version, locktime, inputs, outputs };
+    tx.txid = tx_to_txid(&tx);
+    return Ok(tx)
 }
 
-// Turns a transaction object into a hex transaction string
+// Turns a transaction object into a hex transaction string (includes witness data if there is any)
 pub fn encode_to_hex(tx: &transaction::Content) -> String {
-    let mut s = String::new();
-    s += &hex::encode((tx.version as u32).to_le_bytes());
-    s += &to_hex_var_int(tx.inputs.len() as u64);
-    for i in 0..tx.inputs.len() {
-        let i = &tx.inputs[i];
-        s += &bytestream::Bytestream::convert_endian(&i.txid);
-        s += &hex::encode((i.vout as u32).to_le_bytes());
-        s += &get_length_prefixed_string(&i.scriptsig);
-        s += &hex::encode((i.sequence as u32).to_le_bytes());
-        
-    }
-    s += &to_hex_var_int(tx.outputs.len() as u64);
-    for i in 0..tx.outputs.len() {
-        let i = &tx.outputs[i];
-        s += &hex::encode(i.value.to_le_bytes());
-        s += &get_length_prefixed_string(&i.scriptpubkey);
-    }
-    s += &hex::encode((tx.locktime as u32).to_le_bytes());
-    return s
-}
-
-// Gets the txid of a transaction object
-pub fn tx_to_txid(tx: &transaction::Content) -> String {
-    let transaction_hex_string = encode_to_hex(tx);
-    let hash = Sha256::digest(Sha256::digest(hex::decode(transaction_hex_string).unwrap()));
-    let txid = bytestream::Bytestream::convert_endian(&hex::encode(hash));
-    return txid
+    return hex::encode(encode(tx))
 }
 
+// Serializes a transaction object (includes witness data if there is any)
+pub fn encode(tx: &transaction::Content) -> Vec<u8> {
+    return serialize(tx, tx.has_witness())
+}
+
+// Serializes a transaction object without witness data (the format hashed for the txid)
+pub fn encode_without_witness(tx: &transaction::Content) -> Vec<u8> {
+    return serialize(tx, false)
+}
+
+fn serialize(tx: &transaction::Content, include_witness: bool) -> Vec<u8> {
+    let mut w = ByteWriter::new();
+    w.write_int(tx.version as u64, 4);
+    if include_witness { w.write_bytes(&[0x00, 0x01]) } // marker and flag
+    w.write_varint(tx.inputs.len() as u64);
+    for i in &tx.inputs {
+        w.write_bytes(i.txid.as_bytes());
+        w.write_int(i.vout as u64, 4);
+        w.write_length_prefixed(i.scriptsig.as_bytes());
+        w.write_int(i.sequence.0 as u64, 4);
+    }
+    w.write_varint(tx.outputs.len() as u64);
+    for o in &tx.outputs {
+        w.write_int(o.value.to_sat(), 8);
+        w.write_length_prefixed(o.scriptpubkey.as_bytes());
+    }
+    if include_witness {
+        for i in &tx.inputs {
+            w.write_varint(i.witness.len() as u64);
+            for item in &i.witness {
+                w.write_length_prefixed(item);
+            }
+        }
+    }
+    w.write_int(tx.locktime.0 as u64, 4);
+    return w.into_bytes()
+}
+
+// Gets the txid of a transaction object (witness data is excluded)
+pub fn tx_to_txid(tx: &transaction::Content) -> transaction::Txid {
+    return transaction::Txid::from_bytes(hash256(&encode_without_witness(tx)))
+}
+
+// Gets the wtxid of a transaction object (equal to the txid if there is no witness data)
+pub fn tx_to_wtxid(tx: &transaction::Content) -> [u8; 32] {
+    return hash256(&encode(tx))
+}
+
+// Turns block hex into an object with readable fields
+pub fn decode_block_from_hex(block_hex_string: &str) -> Result<block::Block, ParseError> {
+    return decode_block(&hex_to_bytes(block_hex_string, "hex string")?)
+}
+
+// Turns a serialized block into an object, checking that the merkle root commits to the transactions
+pub fn decode_block(block_bytes: &[u8]) -> Result<block::Block, ParseError> {
+    let mut stream = Bytestream::new(block_bytes);
+    let header = decode_block_header_from_stream(&mut stream)?;
+    let offset = stream.offset();
+    let no_of_transactions = stream.get_varint("transaction count")?;
+    if no_of_transactions == 0 { return Err(ParseError::new(offset, "transaction count", "block has no transactions")) }
+    let mut transactions = vec![];
+    for _ in 0..no_of_transactions {
+        transactions.push(decode_from_stream(&mut stream)?);
+    }
+    stream.expect_end("end of block")?;
+    let txids: Vec<[u8; 32]> = transactions.iter().map(|tx| *tx.txid.as_bytes()).collect();
+    if get_merkle_root(&txids) != header.merkle_root {
+        return Err(ParseError::new(36, "merkle root", "doesn't match the transactions"))
+    }
+    return Ok(block::Block { header, transactions })
+}
+
+// Turns a serialized 80 byte header into an object
+pub fn decode_block_header(header_bytes: &[u8]) -> Result<block::BlockHeader, ParseError> {
+    let mut stream = Bytestream::new(header_bytes);
+    let header = decode_block_header_from_stream(&mut stream)?;
+    stream.expect_end("end of block header")?;
+    return Ok(header)
+}
+
+fn decode_block_header_from_stream(header: &mut Bytestream) -> Result<block::BlockHeader, ParseError> {
+    return Ok(block::BlockHeader {
+        version:         header.get_int(4, "block version")?,
+        prev_blockhash:  header.get_hash("previous block hash")?,
+        merkle_root:     header.get_hash("merkle root")?,
+        time:            header.get_int(4, "block time")?,
+        bits:            header.get_int(4, "block bits")?,
+        nonce:           header.get_int(4, "block nonce")?
+    })
+}
+
+// Turns a block object into a hex block string
+pub fn encode_block_to_hex(block: &block::Block) -> String {
+    return hex::encode(encode_block(block))
+}
+
+// Serializes a block object (transactions include their witness data if they have any)
+pub fn encode_block(block: &block::Block) -> Vec<u8> {
+    let mut w = ByteWriter::new();
+    w.write_bytes(&encode_block_header(&block.header));
+    w.write_varint(block.transactions.len() as u64);
+    for tx in &block.transactions {
+        w.write_bytes(&encode(tx));
+    }
+    return w.into_bytes()
+}
+
+// Serializes a block header (always 80 bytes)
+pub fn encode_block_header(header: &block::BlockHeader) -> Vec<u8> {
+    let mut w = ByteWriter::new();
+    w.write_int(header.version, 4);
+    w.write_bytes(&header.prev_blockhash);
+    w.write_bytes(&header.merkle_root);
+    w.write_int(header.time, 4);
+    w.write_int(header.bits, 4);
+    w.write_int(header.nonce, 4);
+    return w.into_bytes()
+}
+
+// Gets the block hash of a header (in serialization order, display it with txid_to_hex)
+pub fn block_header_to_hash(header: &block::BlockHeader) -> [u8; 32] {
+    return hash256(&encode_block_header(header))
+}
+
+// Calculates the merkle root of a list of txids, duplicating the last hash of a level if it's odd
+pub fn get_merkle_root(txids: &[[u8; 32]]) -> [u8; 32] {
+    if txids.is_empty() { return [0; 32] }
+    let mut level = txids.to_vec();
+    while level.len() > 1 {
+        if level.len() % 2 == 1 { level.push(level[level.len()-1]) }
+        level = level.chunks(2).map(|pair| hash256(&[pair[0], pair[1]].concat())).collect();
+    }
+    return level[0]
+}
+
+// Gets the weight as defined in BIP141 (non-witness bytes count 4 times, witness bytes once)
+pub fn get_weight(tx: &transaction::Content) -> u64 {
+    let base_size = encode_without_witness(tx).len() as u64;
+    let total_size = if tx.has_witness() { encode(tx).len() as u64 } else { base_size };
+    return base_size*3 + total_size
+}
+
+// Gets the virtual size in vbytes (weight/4 rounded up), which is what fee rates are based on
+pub fn get_vsize(tx: &transaction::Content) -> u64 {
+    return get_weight(tx).div_ceil(4)
+}
+
+// Gets the fee rate in sat/vB, given the values of the outputs being spent (in input order)
+pub fn get_fee_rate(tx: &transaction::Content, prevout_values: &[transaction::Amount]) -> Option<f64> {
+    let fee = tx.fee(prevout_values)?;
+    return Some(fee.to_sat() as f64 / get_vsize(tx) as f64)
+}
+
+// Sighash flags, the last byte of a signature (ANYONECANPAY is combined with one of the others)
+pub const SIGHASH_ALL: u32 = 0x01;
+pub const SIGHASH_NONE: u32 = 0x02;
+pub const SIGHASH_SINGLE: u32 = 0x03;
+pub const SIGHASH_ANYONECANPAY: u32 = 0x80;
+
 // Calculates the (unhashed) sighash_all data for a transaction object
-pub fn get_sighash_all_data(tx: &transaction::Content, input_index: u64, input_scriptpubkey: &str) -> String {
-    let mut s = String::new();
-    s += &hex::encode((tx.version as u32).to_le_bytes());
-    s += &to_hex_var_int(tx.inputs.len() as u64);
-    for i0 in 0..tx.inputs.len() {
-        let i = &tx.inputs[i0];
-        s += &bytestream::Bytestream::convert_endian(&i.txid);
-        s += &hex::encode((i.vout as u32).to_le_bytes());
-        if i0 as u64==input_index { // target input gets scriptpubkey
-            s += &get_length_prefixed_string(&input_scriptpubkey);
+pub fn get_sighash_all_data(tx: &transaction::Content, input_index: u64, input_scriptpubkey: &[u8]) -> Vec<u8> {
+    return get_sighash_data(tx, input_index, input_scriptpubkey, SIGHASH_ALL).unwrap()
+}
+
+// Calculates the (unhashed) legacy sighash data for any sighash type
+// None if SIGHASH_SINGLE has no matching output (or the input doesn't exist), see get_legacy_sighash
+pub fn get_sighash_data(tx: &transaction::Content, input_index: u64, script_code: &[u8], sighash_type: u32) -> Option<Vec<u8>> {
+    let input_index = input_index as usize;
+    let base_type = sighash_type & 0x1f;
+    let anyone_can_pay = sighash_type & SIGHASH_ANYONECANPAY != 0;
+    if input_index >= tx.inputs.len() { return None }
+    if base_type == SIGHASH_SINGLE && input_index >= tx.outputs.len() { return None }
+    let script_code = Script::new(script_code.to_vec()).without_codeseparators();
+
+    let mut w = ByteWriter::new();
+    w.write_int(tx.version as u64, 4);
+    // ANYONECANPAY only commits to the input being signed
+    let inputs: Vec<(usize, &transaction::Input)> = if anyone_can_pay { vec![(input_index, &tx.inputs[input_index])] } else { tx.inputs.iter().enumerate().collect() };
+    w.write_varint(inputs.len() as u64);
+    for (i0, i) in inputs {
+        w.write_bytes(i.txid.as_bytes());
+        w.write_int(i.vout as u64, 4);
+        if i0==input_index { // target input gets the script code
+            w.write_length_prefixed(script_code.as_bytes());
+        }
+        else { w.write_varint(0) } // else the scriptsig remains empty (length of 0, hence 0x00)
+        // NONE and SINGLE let others update their sequence
+        if i0 != input_index && (base_type == SIGHASH_NONE || base_type == SIGHASH_SINGLE) { w.write_int(0, 4) }
+        else { w.write_int(i.sequence.0 as u64, 4) }
+    }
+    if base_type == SIGHASH_NONE { w.write_varint(0) }
+    else if base_type == SIGHASH_SINGLE { // outputs before ours are blanked (value -1, empty script)
+        w.write_varint(input_index as u64 + 1);
+        for _ in 0..input_index {
+            w.write_int(u64::MAX, 8);
+            w.write_varint(0);
+        }
+        w.write_int(tx.outputs[input_index].value.to_sat(), 8);
+        w.write_length_prefixed(tx.outputs[input_index].scriptpubkey.as_bytes());
+    }
+    else {
+        w.write_varint(tx.outputs.len() as u64);
+        for o in &tx.outputs {
+            w.write_int(o.value.to_sat(), 8);
+            w.write_length_prefixed(o.scriptpubkey.as_bytes());
+        }
+    }
+    w.write_int(tx.locktime.0 as u64, 4);
+    w.write_int(sighash_type as u64, 4); // end on the full 4 byte sighash flag
+    return Some(w.into_bytes())
+}
+
+// Gets the legacy sighash that gets signed
+// Includes the SIGHASH_SINGLE bug: without a matching output the hash is 1, so the signature is valid for any tx
+pub fn get_legacy_sighash(tx: &transaction::Content, input_index: u64, script_code: &[u8], sighash_type: u32) -> [u8; 32] {
+    return match get_sighash_data(tx, input_index, script_code, sighash_type) {
+        Some(data) => hash256(&data),
+        None => { let mut one = [0; 32]; one[0] = 1; one }
+    }
+}
+
+// Calculates the (unhashed) BIP143 sighash data for spending a segwit v0 output worth `value` sats
+// For p2wsh the script code is the witness script, for p2wpkh it's the matching p2pkh script
+pub fn get_segwit_v0_sighash_data(tx: &transaction::Content, input_index: u64, script_code: &[u8], value: u64, sighash_type: u32) -> Vec<u8> {
+    let input_index = input_index as usize;
+    let base_type = sighash_type & 0x1f;
+    let anyone_can_pay = sighash_type & SIGHASH_ANYONECANPAY != 0;
+    let input = &tx.inputs[input_index];
+
+    let mut hash_prevouts = [0; 32];
+    if !anyone_can_pay {
+        let mut w = ByteWriter::new();
+        for i in &tx.inputs {
+            w.write_bytes(i.txid.as_bytes());
+            w.write_int(i.vout as u64, 4);
+        }
+        hash_prevouts = hash256(&w.into_bytes());
+    }
+    let mut hash_sequence = [0; 32];
+    if !anyone_can_pay && base_type != SIGHASH_SINGLE && base_type != SIGHASH_NONE {
+        let mut w = ByteWriter::new();
+        for i in &tx.inputs { w.write_int(i.sequence.0 as u64, 4) }
+        hash_sequence = hash256(&w.into_bytes());
+    }
+    let mut hash_outputs = [0; 32];
+    if base_type != SIGHASH_SINGLE && base_type != SIGHASH_NONE {
+        let mut w = ByteWriter::new();
+        for o in &tx.outputs {
+            w.write_int(o.value.to_sat(), 8);
+            w.write_length_prefixed(o.scriptpubkey.as_bytes());
         }
-        else { s += "00" } // else the scriptsig remains empty (length of 0, hence 0x00)
-        s += &hex::encode((i.sequence as u32).to_le_bytes());
-        
+        hash_outputs = hash256(&w.into_bytes());
     }
-    s += &to_hex_var_int(tx.outputs.len() as u64);
-    for i in 0..tx.outputs.len() {
-        let i = &tx.outputs[i];
-        s += &hex::encode(i.value.to_le_bytes());
-        s += &get_length_prefixed_string(&i.scriptpubkey);
+    else if base_type == SIGHASH_SINGLE && input_index < tx.outputs.len() { // no "one" bug here, just zeros
+        let mut w = ByteWriter::new();
+        w.write_int(tx.outputs[input_index].value.to_sat(), 8);
+        w.write_length_prefixed(tx.outputs[input_index].scriptpubkey.as_bytes());
+        hash_outputs = hash256(&w.into_bytes());
     }
-    s += &hex::encode((tx.locktime as u32).to_le_bytes());
-    s += &hex::encode((1 as u32).to_le_bytes()); // end on 4 byte sighash_all flag
-    return s
+
+    let mut w = ByteWriter::new();
+    w.write_int(tx.version as u64, 4);
+    w.write_bytes(&hash_prevouts);
+    w.write_bytes(&hash_sequence);
+    w.write_bytes(input.txid.as_bytes());
+    w.write_int(input.vout as u64, 4);
+    w.write_length_prefixed(script_code);
+    w.write_int(value, 8);
+    w.write_int(input.sequence.0 as u64, 4);
+    w.write_bytes(&hash_outputs);
+    w.write_int(tx.locktime.0 as u64, 4);
+    w.write_int(sighash_type as u64, 4);
+    return w.into_bytes()
+}
+
+// Gets the BIP143 sighash that gets signed
+pub fn get_segwit_v0_sighash(tx: &transaction::Content, input_index: u64, script_code: &[u8], value: u64, sighash_type: u32) -> [u8; 32] {
+    return hash256(&get_segwit_v0_sighash_data(tx, input_index, script_code, value, sighash_type))
+}
+
+// BIP340 tagged hash: sha256(sha256(tag) || sha256(tag) || data)
+pub fn tagged_hash(tag: &str, data: &[u8]) -> [u8; 32] {
+    let tag_hash = Sha256::digest(tag.as_bytes());
+    return Sha256::new().chain_update(tag_hash).chain_update(tag_hash).chain_update(data).finalize().into()
+}
+
+// Taproot script leaves are always version 0xc0 here
+pub const TAPROOT_LEAF_VERSION: u8 = 0xc0;
+
+// Gets the hash of a tapscript leaf (with a single leaf this is also the merkle root)
+pub fn get_tapleaf_hash(script: &[u8]) -> [u8; 32] {
+    return get_tapleaf_hash_for_version(TAPROOT_LEAF_VERSION, script)
+}
+
+// Gets the hash of a leaf with any leaf version, as needed to check a control block
+pub fn get_tapleaf_hash_for_version(leaf_version: u8, script: &[u8]) -> [u8; 32] {
+    let mut w = ByteWriter::new();
+    w.write_int(leaf_version as u64, 1);
+    w.write_length_prefixed(script);
+    return tagged_hash("TapLeaf", &w.into_bytes())
+}
+
+// Gets the tweak that's added to the internal key to commit to the script tree
+pub fn get_taptweak(internal_key: &[u8; 32], merkle_root: &[u8; 32]) -> [u8; 32] {
+    return tagged_hash("TapTweak", &[&internal_key[..], &merkle_root[..]].concat())
+}
+
+// Taproot only: signs like SIGHASH_ALL but the signature has no sighash byte
+pub const SIGHASH_DEFAULT: u32 = 0x00;
+
+// Calculates the (untagged) BIP341 sighash data, prevouts are the outputs spent by every input (in input order)
+// leaf_hash is None for a key path spend and the tapleaf hash for a script path spend (no annex, no codeseparator)
+// None if the sighash type is invalid, a prevout is missing or SIGHASH_SINGLE has no matching output
+pub fn get_taproot_sighash_data(tx: &transaction::Content, input_index: u64, prevouts: &[transaction::Output], leaf_hash: Option<&[u8; 32]>, sighash_type: u32) -> Option<Vec<u8>> {
+    return taproot_sighash_data(tx, input_index, prevouts, leaf_hash.map(|hash| (hash, u32::MAX)), sighash_type)
 }
 
-// Turns an int into a hex varint string
-fn to_hex_var_int(value: u64) -> String {
-    return if value < (u8::MAX-2) as u64 {
-        hex::encode((value as u8).to_ne_bytes())
+// Same, but the script path commits to the position of the last executed OP_CODESEPARATOR (u32::MAX for none)
+fn taproot_sighash_data(tx: &transaction::Content, input_index: u64, prevouts: &[transaction::Output], leaf: Option<(&[u8; 32], u32)>, sighash_type: u32) -> Option<Vec<u8>> {
+    let input_index = input_index as usize;
+    let base_type = sighash_type & 0x03;
+    let anyone_can_pay = sighash_type & SIGHASH_ANYONECANPAY != 0;
+    if sighash_type > 0x03 && !(0x81..=0x83).contains(&sighash_type) { return None }
+    if input_index >= tx.inputs.len() || prevouts.len() != tx.inputs.len() { return None }
+    if base_type == SIGHASH_SINGLE && input_index >= tx.outputs.len() { return None }
+    let input = &tx.inputs[input_index];
+
+    let mut w = ByteWriter::new();
+    w.write_int(0, 1); // epoch
+    w.write_int(sighash_type as u64, 1);
+    w.write_int(tx.version as u64, 4);
+    w.write_int(tx.locktime.0 as u64, 4);
+    if !anyone_can_pay {
+        let (mut outpoints, mut amounts, mut scriptpubkeys, mut sequences) = (ByteWriter::new(), ByteWriter::new(), ByteWriter::new(), ByteWriter::new());
+        for (i, prevout) in tx.inputs.iter().zip(prevouts) {
+            outpoints.write_bytes(i.txid.as_bytes());
+            outpoints.write_int(i.vout as u64, 4);
+            amounts.write_int(prevout.value.to_sat(), 8);
+            scriptpubkeys.write_length_prefixed(prevout.scriptpubkey.as_bytes());
+            sequences.write_int(i.sequence.0 as u64, 4);
+        }
+        for hashed in [outpoints, amounts, scriptpubkeys, sequences] {
+            w.write_bytes(&Sha256::digest(hashed.into_bytes()));
+        }
+    }
+    if base_type != SIGHASH_NONE && base_type != SIGHASH_SINGLE {
+        let mut outputs = ByteWriter::new();
+        for o in &tx.outputs {
+            outputs.write_int(o.value.to_sat(), 8);
+            outputs.write_length_prefixed(o.scriptpubkey.as_bytes());
+        }
+        w.write_bytes(&Sha256::digest(outputs.into_bytes()));
     }
-    else if value < u16::MAX as u64 {
-        let byte_one = hex::encode((u8::MAX-2).to_ne_bytes());
-        hex::encode((value as u16).to_le_bytes()) + &byte_one
+    w.write_int(if leaf.is_some() { 2 } else { 0 }, 1); // spend type: ext_flag*2 + annex_present
+    if anyone_can_pay {
+        w.write_bytes(input.txid.as_bytes());
+        w.write_int(input.vout as u64, 4);
+        w.write_int(prevouts[input_index].value.to_sat(), 8);
+        w.write_length_prefixed(prevouts[input_index].scriptpubkey.as_bytes());
+        w.write_int(input.sequence.0 as u64, 4);
     }
-    else if value < u32::MAX as u64 {
-        let byte_one = hex::encode((u8::MAX-1).to_ne_bytes());
-        hex::encode((value as u32).to_le_bytes()) + &byte_one
+    else {
+        w.write_int(input_index as u64, 4);
     }
-    else { // 64 bit value
-        let byte_one = hex::encode((u8::MAX).to_ne_bytes());
-        hex::encode((value as u64).to_le_bytes()) + &byte_one
+    if base_type == SIGHASH_SINGLE {
+        let mut output = ByteWriter::new();
+        output.write_int(tx.outputs[input_index].value.to_sat(), 8);
+        output.write_length_prefixed(tx.outputs[input_index].scriptpubkey.as_bytes());
+        w.write_bytes(&Sha256::digest(output.into_bytes()));
     }
+    if let Some((leaf_hash, codeseparator_position)) = leaf {
+        w.write_bytes(leaf_hash);
+        w.write_int(0, 1); // key version
+        w.write_int(codeseparator_position as u64, 4);
+    }
+    return Some(w.into_bytes())
+}
+
+// Gets the BIP341 sighash that gets signed
+pub fn get_taproot_sighash(tx: &transaction::Content, input_index: u64, prevouts: &[transaction::Output], leaf_hash: Option<&[u8; 32]>, sighash_type: u32) -> Option<[u8; 32]> {
+    return get_taproot_sighash_data(tx, input_index, prevouts, leaf_hash, sighash_type).map(|data| tagged_hash("TapSighash", &data))
 }
 
-// Prefixes a hex string with its length in bytes
-pub fn get_length_prefixed_string(s: &str) -> String {
-    if s.len() == 0 { return "00".to_string() }
-    return to_hex_var_int((s.len()/2) as u64) + s
-}
\ No newline at end of file
+// Gets the sighash for a signature checked by a tapscript, codeseparator_position is the opcode index of the last
+// executed OP_CODESEPARATOR (u32::MAX if there was none)
+pub fn get_tapscript_sighash(tx: &transaction::Content, input_index: u64, prevouts: &[transaction::Output], leaf_hash: &[u8; 32], codeseparator_position: u32, sighash_type: u32) -> Option<[u8; 32]> {
+    return taproot_sighash_data(tx, input_index, prevouts, Some((leaf_hash, codeseparator_position)), sighash_type).map(|data| tagged_hash("TapSighash", &data))
+}
+
+// Prefixes bytes with their compact size length, for serialization only (pushes inside scripts use Builder::push_bytes)
+pub fn get_length_prefixed(bytes: &[u8]) -> Vec<u8> {
+    let mut w = ByteWriter::new();
+    w.write_length_prefixed(bytes);
+    return w.into_bytes()
+}
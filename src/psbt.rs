@@ -0,0 +1,270 @@
+use super::transaction;
+use super::parser::{self, ParseError, Bytestream, ByteWriter, Script};
+
+// A BIP174 (version 0) partially signed transaction, the scriptsigs and witnesses of tx are always empty
+// Fields this software doesn't use (e.g. bip32 derivations) are kept as raw key/value pairs
+#[derive(Debug)]
+pub struct Psbt {
+    pub tx: transaction::Content, pub inputs: Vec<Input>, pub outputs: Vec<Output>, pub unknown: Vec<Pair>
+}
+
+#[derive(Debug, Default)]
+pub struct Input {
+    pub non_witness_utxo: Option<transaction::Content>, // the whole tx that created the output being spent
+    pub witness_utxo: Option<transaction::Output>,
+    pub partial_sigs: Vec<Pair>, // (pubkey, signature)
+    pub sighash_type: Option<u32>,
+    pub redeem_script: Option<Script>,
+    pub witness_script: Option<Script>,
+    pub final_scriptsig: Option<Script>,
+    pub final_scriptwitness: Option<Vec<Vec<u8>>>,
+    pub unknown: Vec<Pair>
+}
+
+#[derive(Debug, Default)]
+pub struct Output {
+    pub redeem_script: Option<Script>, pub witness_script: Option<Script>, pub unknown: Vec<Pair>
+}
+
+// A raw key/value pair (the key includes its type byte)
+pub type Pair = (Vec<u8>, Vec<u8>);
+
+const MAGIC: &[u8] = b"psbt\xff";
+
+impl Psbt {
+    // Wraps a transaction, any scriptsigs and witnesses it already has become finalized inputs
+    pub fn from_tx(tx: &transaction::Content) -> Psbt {
+        let mut inputs = vec![];
+        let mut unsigned_tx = transaction::Content { txid: tx.txid, version: tx.version, locktime: tx.locktime, inputs: tx.inputs.clone(), outputs: tx.outputs.clone() };
+        for i in unsigned_tx.inputs.iter_mut() {
+            let mut input = Input::default();
+            if !i.scriptsig.is_empty() { input.final_scriptsig = Some(std::mem::take(&mut i.scriptsig)) }
+            if !i.witness.is_empty() { input.final_scriptwitness = Some(std::mem::take(&mut i.witness)) }
+            inputs.push(input);
+        }
+        unsigned_tx.txid = parser::tx_to_txid(&unsigned_tx);
+        let outputs = tx.outputs.iter().map(|_| Output::default()).collect();
+        return Psbt { tx: unsigned_tx, inputs, outputs, unknown: vec![] }
+    }
+
+    // Gets the transaction with the finalized scriptsigs and witnesses filled in (unfinalized inputs stay empty)
+    pub fn extract_tx(&self) -> transaction::Content {
+        let mut tx = transaction::Content { txid: self.tx.txid, version: self.tx.version, locktime: self.tx.locktime, inputs: self.tx.inputs.clone(), outputs: self.tx.outputs.clone() };
+        for (i, input) in tx.inputs.iter_mut().zip(&self.inputs) {
+            i.scriptsig = input.final_scriptsig.clone().unwrap_or_default();
+            i.witness = input.final_scriptwitness.clone().unwrap_or_default();
+        }
+        tx.txid = parser::tx_to_txid(&tx);
+        return tx
+    }
+
+    // Gets the output an input spends, if the psbt includes it
+    pub fn input_utxo(&self, input_index: usize) -> Option<transaction::Output> {
+        let input = self.inputs.get(input_index)?;
+        if let Some(utxo) = &input.witness_utxo { return Some(utxo.clone()) }
+        let vout = self.tx.inputs[input_index].vout as usize;
+        return input.non_witness_utxo.as_ref().and_then(|tx| tx.outputs.get(vout)).cloned()
+    }
+
+    // Gets the value of the output an input spends, if the psbt includes it
+    pub fn input_value(&self, input_index: usize) -> Option<transaction::Amount> {
+        return self.input_utxo(input_index).map(|o| o.value)
+    }
+}
+
+// Turns a base64 psbt (as used by Core) into an object
+pub fn decode_from_base64(psbt_string: &str) -> Result<Psbt, ParseError> {
+    return decode(&base64_decode(psbt_string)?)
+}
+
+// Turns a serialized psbt into an object
+pub fn decode(psbt_bytes: &[u8]) -> Result<Psbt, ParseError> {
+    let mut psbt = Bytestream::new(psbt_bytes);
+    if psbt.get_bytes(5, "psbt magic")? != MAGIC { return Err(ParseError::new(0, "psbt magic", "not a psbt")) }
+
+    let mut tx = None;
+    let mut unknown = vec![];
+    for (offset, (key, value)) in get_map(&mut psbt)? {
+        match key[0] {
+            0x00 if key.len() == 1 => {
+                let unsigned_tx = parser::decode(&value).map_err(in_value(offset, "unsigned tx"))?;
+                if unsigned_tx.inputs.iter().any(|i| !i.scriptsig.is_empty() || !i.witness.is_empty()) {
+                    return Err(ParseError::new(offset, "unsigned tx", "has scriptsigs or witnesses"))
+                }
+                tx = Some(unsigned_tx);
+            }
+            0xfb if key.len() == 1 => {
+                if value != [0; 4] { return Err(ParseError::new(offset, "psbt version", "only version 0 is supported")) }
+                unknown.push((key, value));
+            }
+            _ => unknown.push((key, value))
+        }
+    }
+    let tx = tx.ok_or_else(|| ParseError::new(psbt.offset(), "unsigned tx", "missing"))?;
+
+    let mut inputs = vec![];
+    for _ in 0..tx.inputs.len() {
+        let mut input = Input::default();
+        for (offset, (key, value)) in get_map(&mut psbt)? {
+            let is_single = key.len() == 1;
+            match key[0] {
+                0x00 if is_single => input.non_witness_utxo = Some(parser::decode(&value).map_err(in_value(offset, "non-witness utxo"))?),
+                0x01 if is_single => {
+                    let mut utxo = Bytestream::new(&value);
+                    let output_value = utxo.get_int(8, "witness utxo").map_err(in_value(offset, "witness utxo"))?;
+                    let output_value = transaction::Amount::from_sat(output_value).ok_or_else(|| ParseError::new(offset, "witness utxo", "value exceeds MAX_MONEY"))?;
+                    let scriptpubkey = Script::new(utxo.get_length_prefixed("witness utxo").map_err(in_value(offset, "witness utxo"))?.to_vec());
+                    utxo.expect_end("witness utxo").map_err(in_value(offset, "witness utxo"))?;
+                    input.witness_utxo = Some(transaction::Output { value: output_value, scriptpubkey });
+                }
+                0x02 => input.partial_sigs.push((key[1..].to_vec(), value)),
+                0x03 if is_single && value.len() == 4 => input.sighash_type = Some(u32::from_le_bytes([value[0], value[1], value[2], value[3]])),
+                0x04 if is_single => input.redeem_script = Some(Script::new(value)),
+                0x05 if is_single => input.witness_script = Some(Script::new(value)),
+                0x07 if is_single => input.final_scriptsig = Some(Script::new(value)),
+                0x08 if is_single => {
+                    let mut witness = Bytestream::new(&value);
+                    let mut items = vec![];
+                    for _ in 0..witness.get_varint("final witness").map_err(in_value(offset, "final witness"))? {
+                        items.push(witness.get_length_prefixed("final witness").map_err(in_value(offset, "final witness"))?.to_vec());
+                    }
+                    witness.expect_end("final witness").map_err(in_value(offset, "final witness"))?;
+                    input.final_scriptwitness = Some(items);
+                }
+                0x00..=0x08 if is_single || key[0] != 0x06 => return Err(ParseError::new(offset, "psbt input", &format!("invalid key or value for type {}", key[0]))),
+                _ => input.unknown.push((key, value))
+            }
+        }
+        inputs.push(input);
+    }
+
+    let mut outputs = vec![];
+    for _ in 0..tx.outputs.len() {
+        let mut output = Output::default();
+        for (_, (key, value)) in get_map(&mut psbt)? {
+            match key[0] {
+                0x00 if key.len() == 1 => output.redeem_script = Some(Script::new(value)),
+                0x01 if key.len() == 1 => output.witness_script = Some(Script::new(value)),
+                _ => output.unknown.push((key, value))
+            }
+        }
+        outputs.push(output);
+    }
+    psbt.expect_end("end of psbt")?;
+    return Ok(Psbt { tx, inputs, outputs, unknown })
+}
+
+// Errors inside a value are reported at the start of its key/value pair
+fn in_value(offset: usize, field: &'static str) -> impl Fn(ParseError) -> ParseError {
+    return move |e| ParseError::new(offset, field, &format!("{} (byte {} of the value)", e.reason, e.offset))
+}
+
+// Reads key/value pairs up to the 0x00 separator, rejecting duplicate keys
+fn get_map(psbt: &mut Bytestream) -> Result<Vec<(usize, Pair)>, ParseError> {
+    let mut map: Vec<(usize, Pair)> = vec![];
+    loop {
+        let offset = psbt.offset();
+        let key = psbt.get_length_prefixed("psbt key")?.to_vec();
+        if key.is_empty() { return Ok(map) }
+        if map.iter().any(|(_, (k, _))| *k == key) { return Err(ParseError::new(offset, "psbt key", "duplicate key")) }
+        let value = psbt.get_length_prefixed("psbt value")?.to_vec();
+        map.push((offset, (key, value)));
+    }
+}
+
+// Turns a psbt object into base64 (what Core's psbt rpcs expect)
+pub fn encode_to_base64(psbt: &Psbt) -> Result<String, ParseError> {
+    return Ok(base64_encode(&encode(psbt)?))
+}
+
+// Serializes a psbt object, each map sorted by key so the output is deterministic
+pub fn encode(psbt: &Psbt) -> Result<Vec<u8>, ParseError> {
+    let mut w = ByteWriter::new();
+    w.write_bytes(MAGIC);
+
+    let mut global = vec![(vec![0x00], parser::encode_without_witness(&psbt.tx))];
+    global.extend(psbt.unknown.iter().cloned());
+    write_map(&mut w, global)?;
+
+    for input in &psbt.inputs {
+        let mut map = input.unknown.clone();
+        if let Some(tx) = &input.non_witness_utxo { map.push((vec![0x00], parser::encode(tx))) }
+        if let Some(utxo) = &input.witness_utxo {
+            let mut value = ByteWriter::new();
+            value.write_int(utxo.value.to_sat(), 8);
+            value.write_length_prefixed(utxo.scriptpubkey.as_bytes());
+            map.push((vec![0x01], value.into_bytes()));
+        }
+        for (pubkey, sig) in &input.partial_sigs { map.push(([&[0x02][..], pubkey].concat(), sig.clone())) }
+        if let Some(sighash_type) = input.sighash_type { map.push((vec![0x03], sighash_type.to_le_bytes().to_vec())) }
+        if let Some(script) = &input.redeem_script { map.push((vec![0x04], script.as_bytes().to_vec())) }
+        if let Some(script) = &input.witness_script { map.push((vec![0x05], script.as_bytes().to_vec())) }
+        if let Some(scriptsig) = &input.final_scriptsig { map.push((vec![0x07], scriptsig.as_bytes().to_vec())) }
+        if let Some(witness) = &input.final_scriptwitness {
+            let mut value = ByteWriter::new();
+            value.write_varint(witness.len() as u64);
+            for item in witness { value.write_length_prefixed(item) }
+            map.push((vec![0x08], value.into_bytes()));
+        }
+        write_map(&mut w, map)?;
+    }
+
+    for output in &psbt.outputs {
+        let mut map = output.unknown.clone();
+        if let Some(script) = &output.redeem_script { map.push((vec![0x00], script.as_bytes().to_vec())) }
+        if let Some(script) = &output.witness_script { map.push((vec![0x01], script.as_bytes().to_vec())) }
+        write_map(&mut w, map)?;
+    }
+    return Ok(w.into_bytes())
+}
+
+// Writes key/value pairs sorted by key, rejecting duplicate keys like get_map does on decode
+fn write_map(w: &mut ByteWriter, mut map: Vec<Pair>) -> Result<(), ParseError> {
+    map.sort_by(|(a, _), (b, _)| a.cmp(b));
+    if map.windows(2).any(|pair| pair[0].0 == pair[1].0) { return Err(ParseError::new(w.len(), "psbt key", "duplicate key")) }
+    for (key, value) in map {
+        w.write_length_prefixed(&key);
+        w.write_length_prefixed(&value);
+    }
+    w.write_int(0, 1); // separator
+    return Ok(())
+}
+
+const BASE64_CHARSET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// Standard base64 with padding
+pub fn base64_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0_u32, |n, (i, byte)| n | (*byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() { encoded.push(BASE64_CHARSET[(n >> (18 - 6 * i) & 63) as usize] as char) }
+            else { encoded.push('=') }
+        }
+    }
+    return encoded
+}
+
+// Decodes standard base64, the length must be a multiple of 4 (padding included)
+pub fn base64_decode(base64_string: &str) -> Result<Vec<u8>, ParseError> {
+    let characters = base64_string.as_bytes();
+    if !characters.len().is_multiple_of(4) { return Err(ParseError::new(characters.len(), "base64 string", "length is not a multiple of 4")) }
+    let padding = characters.iter().rev().take_while(|c| **c == b'=').count();
+    if padding > 2 { return Err(ParseError::new(characters.len() - padding, "base64 string", "too much padding")) }
+    let mut bytes = vec![];
+    for (chunk_index, chunk) in characters.chunks(4).enumerate() {
+        let mut n = 0_u32;
+        for (i, c) in chunk.iter().enumerate() {
+            let offset = chunk_index * 4 + i;
+            let value = match BASE64_CHARSET.iter().position(|x| x == c) {
+                Some(value) => value as u32,
+                None if *c == b'=' && offset >= characters.len() - padding => 0,
+                None => return Err(ParseError::new(offset, "base64 string", &format!("invalid character '{}'", *c as char)))
+            };
+            n = n << 6 | value;
+        }
+        bytes.extend_from_slice(&n.to_be_bytes()[1..]);
+    }
+    bytes.truncate(bytes.len() - padding);
+    return Ok(bytes)
+}
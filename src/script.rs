@@ -0,0 +1,346 @@
+use super::{Bytestream, ParseError};
+
+// Defines the named opcodes once, so byte values, names and lookups can't drift apart
+macro_rules! opcodes {
+    ($($name:ident = $byte:expr),* $(,)?) => {
+        #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        #[repr(u8)]
+        pub enum Opcode { $($name = $byte),* }
+
+        impl Opcode {
+            // Gets the named opcode for a byte (None for OP_PUSHBYTES_1-75 and unassigned bytes)
+            pub fn from_u8(byte: u8) -> Option<Opcode> {
+                return match byte { $($byte => Some(Opcode::$name),)* _ => None }
+            }
+
+            // Full name as used by Core, e.g. OP_CHECKSIG
+            pub fn name(self) -> &'static str {
+                return match self { $(Opcode::$name => stringify!($name)),* }
+            }
+
+            pub fn from_name(name: &str) -> Option<Opcode> {
+                return match name { $(stringify!($name) => Some(Opcode::$name),)* _ => None }
+            }
+        }
+    }
+}
+
+opcodes! {
+    OP_0 = 0x00, OP_PUSHDATA1 = 0x4c, OP_PUSHDATA2 = 0x4d, OP_PUSHDATA4 = 0x4e, OP_1NEGATE = 0x4f, OP_RESERVED = 0x50,
+    OP_1 = 0x51, OP_2 = 0x52, OP_3 = 0x53, OP_4 = 0x54, OP_5 = 0x55, OP_6 = 0x56, OP_7 = 0x57, OP_8 = 0x58,
+    OP_9 = 0x59, OP_10 = 0x5a, OP_11 = 0x5b, OP_12 = 0x5c, OP_13 = 0x5d, OP_14 = 0x5e, OP_15 = 0x5f, OP_16 = 0x60,
+    // control
+    OP_NOP = 0x61, OP_VER = 0x62, OP_IF = 0x63, OP_NOTIF = 0x64, OP_VERIF = 0x65, OP_VERNOTIF = 0x66,
+    OP_ELSE = 0x67, OP_ENDIF = 0x68, OP_VERIFY = 0x69, OP_RETURN = 0x6a,
+    // stack
+    OP_TOALTSTACK = 0x6b, OP_FROMALTSTACK = 0x6c, OP_2DROP = 0x6d, OP_2DUP = 0x6e, OP_3DUP = 0x6f, OP_2OVER = 0x70,
+    OP_2ROT = 0x71, OP_2SWAP = 0x72, OP_IFDUP = 0x73, OP_DEPTH = 0x74, OP_DROP = 0x75, OP_DUP = 0x76, OP_NIP = 0x77,
+    OP_OVER = 0x78, OP_PICK = 0x79, OP_ROLL = 0x7a, OP_ROT = 0x7b, OP_SWAP = 0x7c, OP_TUCK = 0x7d,
+    // splice
+    OP_CAT = 0x7e, OP_SUBSTR = 0x7f, OP_LEFT = 0x80, OP_RIGHT = 0x81, OP_SIZE = 0x82,
+    // bit logic
+    OP_INVERT = 0x83, OP_AND = 0x84, OP_OR = 0x85, OP_XOR = 0x86, OP_EQUAL = 0x87, OP_EQUALVERIFY = 0x88,
+    OP_RESERVED1 = 0x89, OP_RESERVED2 = 0x8a,
+    // arithmetic
+    OP_1ADD = 0x8b, OP_1SUB = 0x8c, OP_2MUL = 0x8d, OP_2DIV = 0x8e, OP_NEGATE = 0x8f, OP_ABS = 0x90, OP_NOT = 0x91,
+    OP_0NOTEQUAL = 0x92, OP_ADD = 0x93, OP_SUB = 0x94, OP_MUL = 0x95, OP_DIV = 0x96, OP_MOD = 0x97, OP_LSHIFT = 0x98,
+    OP_RSHIFT = 0x99, OP_BOOLAND = 0x9a, OP_BOOLOR = 0x9b, OP_NUMEQUAL = 0x9c, OP_NUMEQUALVERIFY = 0x9d,
+    OP_NUMNOTEQUAL = 0x9e, OP_LESSTHAN = 0x9f, OP_GREATERTHAN = 0xa0, OP_LESSTHANOREQUAL = 0xa1,
+    OP_GREATERTHANOREQUAL = 0xa2, OP_MIN = 0xa3, OP_MAX = 0xa4, OP_WITHIN = 0xa5,
+    // crypto
+    OP_RIPEMD160 = 0xa6, OP_SHA1 = 0xa7, OP_SHA256 = 0xa8, OP_HASH160 = 0xa9, OP_HASH256 = 0xaa,
+    OP_CODESEPARATOR = 0xab, OP_CHECKSIG = 0xac, OP_CHECKSIGVERIFY = 0xad, OP_CHECKMULTISIG = 0xae,
+    OP_CHECKMULTISIGVERIFY = 0xaf,
+    // expansion
+    OP_NOP1 = 0xb0, OP_CHECKLOCKTIMEVERIFY = 0xb1, OP_CHECKSEQUENCEVERIFY = 0xb2, OP_NOP4 = 0xb3, OP_NOP5 = 0xb4,
+    OP_NOP6 = 0xb5, OP_NOP7 = 0xb6, OP_NOP8 = 0xb7, OP_NOP9 = 0xb8, OP_NOP10 = 0xb9,
+    OP_CHECKSIGADD = 0xba, OP_INVALIDOPCODE = 0xff,
+}
+
+impl Opcode {
+    // Name as shown in Core's asm (small numbers are shown as numbers)
+    pub fn asm_name(self) -> String {
+        let byte = self as u8;
+        return match self {
+            Opcode::OP_0 => "0".to_string(),
+            Opcode::OP_1NEGATE => "-1".to_string(),
+            _ if (Opcode::OP_1 as u8..=Opcode::OP_16 as u8).contains(&byte) => (byte - 0x50).to_string(),
+            _ => self.name().to_string()
+        }
+    }
+}
+
+// A single script element: pushed data (any of OP_0 up to OP_PUSHDATA4) or a non-push opcode
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction {
+    Push(Vec<u8>), Op(Opcode), Unknown(u8)
+}
+
+// Raw script bytes, parsed into instructions on demand so that non-standard encodings survive a round trip
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Script {
+    bytes: Vec<u8>
+}
+
+impl Script {
+    pub fn new(bytes: Vec<u8>) -> Script {
+        return Script { bytes }
+    }
+
+    pub fn from_hex(script_hex_string: &str) -> Result<Script, ParseError> {
+        return Ok(Script::new(super::hex_to_bytes(script_hex_string, "script")?))
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        return &self.bytes
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        return self.bytes
+    }
+
+    pub fn len(&self) -> usize {
+        return self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.bytes.is_empty()
+    }
+
+    // Assembles a script from asm text, see Builder::push_asm for the accepted tokens
+    pub fn from_asm(asm: &str) -> Result<Script, ParseError> {
+        return Ok(Builder::new().push_asm(asm)?.into_script())
+    }
+
+    pub fn to_hex(&self) -> String {
+        return hex::encode(&self.bytes)
+    }
+
+    // Splits the script into instructions, fails if a push runs past the end
+    pub fn instructions(&self) -> Result<Vec<Instruction>, ParseError> {
+        let (instructions, error) = self.parse();
+        return match error { Some(e) => Err(e), None => Ok(instructions) }
+    }
+
+    // Renders the script like Core's asm output (pushes of up to 4 bytes are shown as numbers)
+    pub fn to_asm(&self) -> String {
+        return self.asm(false)
+    }
+
+    // Renders a scriptsig like Core does, signatures show their sighash type (e.g. [ALL]) instead of the last byte
+    pub fn to_scriptsig_asm(&self) -> String {
+        return self.asm(!self.is_unspendable())
+    }
+
+    // Starts with OP_RETURN or is too big to ever be executed
+    pub fn is_unspendable(&self) -> bool {
+        return self.bytes.first() == Some(&(Opcode::OP_RETURN as u8)) || self.bytes.len() > 10_000
+    }
+
+    fn asm(&self, attempt_sighash_decode: bool) -> String {
+        let (instructions, error) = self.parse();
+        let mut asm: Vec<String> = instructions.iter().map(|instruction| match instruction {
+            Instruction::Push(data) if data.len() <= 4 => decode_script_num(data).to_string(),
+            Instruction::Push(data) if attempt_sighash_decode && is_valid_signature_encoding(data) && sighash_type_name(data[data.len()-1]).is_some() => {
+                format!("{}[{}]", hex::encode(&data[..data.len()-1]), sighash_type_name(data[data.len()-1]).unwrap())
+            }
+            Instruction::Push(data) => hex::encode(data),
+            Instruction::Op(opcode) => opcode.asm_name(),
+            Instruction::Unknown(_) => "OP_UNKNOWN".to_string()
+        }).collect();
+        if error.is_some() { asm.push("[error]".to_string()) } // Core also shows everything up to the bad push
+        return asm.join(" ")
+    }
+
+    // Removes every OP_CODESEPARATOR and keeps everything else byte for byte, like Core does for legacy sighashes
+    // (bytes after a malformed push are kept as they are)
+    pub fn without_codeseparators(&self) -> Script {
+        let mut stream = Bytestream::new(&self.bytes);
+        let mut bytes = vec![];
+        while stream.remaining() > 0 {
+            let start = stream.offset();
+            match Script::next_instruction(&mut stream) {
+                Ok(Instruction::Op(Opcode::OP_CODESEPARATOR)) => {}
+                Ok(_) => bytes.extend_from_slice(&self.bytes[start..stream.offset()]),
+                Err(_) => { bytes.extend_from_slice(&self.bytes[start..]); break }
+            }
+        }
+        return Script::new(bytes)
+    }
+
+    // Parses instructions up to the end or the first malformed push
+    fn parse(&self) -> (Vec<Instruction>, Option<ParseError>) {
+        let mut stream = Bytestream::new(&self.bytes);
+        let mut instructions = vec![];
+        while stream.remaining() > 0 {
+            match Script::next_instruction(&mut stream) {
+                Ok(instruction) => instructions.push(instruction),
+                Err(e) => return (instructions, Some(e))
+            }
+        }
+        return (instructions, None)
+    }
+
+    // Reads the instruction at the stream's position, used to step through a script while executing it
+    pub fn next_instruction(stream: &mut Bytestream) -> Result<Instruction, ParseError> {
+        let byte = stream.get_int(1, "opcode")? as u8;
+        let size = match byte {
+            0x00..=0x4b => byte as u64,
+            0x4c => stream.get_int(1, "OP_PUSHDATA1 size")?,
+            0x4d => stream.get_int(2, "OP_PUSHDATA2 size")?,
+            0x4e => stream.get_int(4, "OP_PUSHDATA4 size")?,
+            _ => return Ok(match Opcode::from_u8(byte) {
+                Some(opcode) => Instruction::Op(opcode),
+                None => Instruction::Unknown(byte)
+            })
+        };
+        return Ok(Instruction::Push(stream.get_bytes(size, "pushed data")?.to_vec()))
+    }
+}
+
+// Checks the strict DER encoding of BIP66 (a signature followed by its sighash byte)
+pub fn is_valid_signature_encoding(sig: &[u8]) -> bool {
+    // 0x30 [total length] 0x02 [R length] [R] 0x02 [S length] [S] [sighash]
+    if sig.len() < 9 || sig.len() > 73 { return false }
+    if sig[0] != 0x30 || sig[1] as usize != sig.len() - 3 { return false }
+    let r_length = sig[3] as usize;
+    if 5 + r_length >= sig.len() { return false }
+    let s_length = sig[5 + r_length] as usize;
+    if r_length + s_length + 7 != sig.len() { return false }
+    // R and S are positive integers without unnecessary leading zero bytes
+    if sig[2] != 0x02 || r_length == 0 || sig[4] & 0x80 != 0 { return false }
+    if r_length > 1 && sig[4] == 0x00 && sig[5] & 0x80 == 0 { return false }
+    if sig[r_length + 4] != 0x02 || s_length == 0 || sig[r_length + 6] & 0x80 != 0 { return false }
+    if s_length > 1 && sig[r_length + 6] == 0x00 && sig[r_length + 7] & 0x80 == 0 { return false }
+    return true
+}
+
+// Name of a defined legacy/segwit v0 sighash type as shown in Core's asm
+pub fn sighash_type_name(sighash_type: u8) -> Option<&'static str> {
+    return match sighash_type {
+        0x01 => Some("ALL"), 0x02 => Some("NONE"), 0x03 => Some("SINGLE"),
+        0x81 => Some("ALL|ANYONECANPAY"), 0x82 => Some("NONE|ANYONECANPAY"), 0x83 => Some("SINGLE|ANYONECANPAY"),
+        _ => None
+    }
+}
+
+// Builds a script from opcodes and pushes, always using the smallest push encoding
+#[derive(Default)]
+pub struct Builder {
+    bytes: Vec<u8>
+}
+
+impl Builder {
+    pub fn new() -> Builder {
+        return Builder { bytes: vec![] }
+    }
+
+    pub fn push_opcode(mut self, opcode: Opcode) -> Builder {
+        self.bytes.push(opcode as u8);
+        return self
+    }
+
+    // Pushes data with the minimal encoding (BIP62), e.g. a single byte of 1-16 becomes OP_1-OP_16
+    pub fn push_bytes(mut self, data: &[u8]) -> Builder {
+        if data.is_empty() { return self.push_opcode(Opcode::OP_0) }
+        if data.len() == 1 && (1..=16).contains(&data[0]) { return self.push_opcode(Opcode::from_u8(0x50 + data[0]).unwrap()) }
+        if data == [0x81] { return self.push_opcode(Opcode::OP_1NEGATE) }
+        let size = data.len();
+        if size <= 0x4b { self.bytes.push(size as u8) }
+        else if size <= 0xff { self.bytes.extend_from_slice(&[Opcode::OP_PUSHDATA1 as u8, size as u8]) }
+        else if size <= 0xffff {
+            self.bytes.push(Opcode::OP_PUSHDATA2 as u8);
+            self.bytes.extend_from_slice(&(size as u16).to_le_bytes());
+        }
+        else {
+            self.bytes.push(Opcode::OP_PUSHDATA4 as u8);
+            self.bytes.extend_from_slice(&(size as u32).to_le_bytes());
+        }
+        self.bytes.extend_from_slice(data);
+        return self
+    }
+
+    // Pushes a number as a script number (OP_0, OP_1NEGATE and OP_1-OP_16 where possible)
+    pub fn push_int(self, number: i64) -> Builder {
+        return self.push_bytes(&encode_script_num(number))
+    }
+
+    // Appends asm tokens separated by whitespace:
+    // decimal numbers (up to 4 script num bytes), hex data pushes, 0x-prefixed raw bytes, 'text' pushes,
+    // and opcode names with or without the OP_ prefix.
+    // Note that hex data that looks like a small number (e.g. 1234567890) is read as a number, use 0x for raw bytes then.
+    pub fn push_asm(mut self, asm: &str) -> Result<Builder, ParseError> {
+        for token in asm.split_whitespace() {
+            let offset = token.as_ptr() as usize - asm.as_ptr() as usize;
+            let digits = token.strip_prefix('-').unwrap_or(token);
+            let is_decimal = !digits.is_empty() && digits.len() <= 10 && digits.chars().all(|c| c.is_ascii_digit());
+            if is_decimal {
+                match token.parse::<i64>() {
+                    Ok(number) if number.abs() <= i32::MAX as i64 => { self = self.push_int(number); continue }
+                    _ if token.starts_with('-') => return Err(ParseError::new(offset, "asm", &format!("number {} out of range", token))),
+                    _ => {} // too big for a number, so it can only be hex data
+                }
+            }
+            if let Some(raw) = token.strip_prefix("0x") {
+                self.bytes.extend(super::hex_to_bytes(raw, "asm raw bytes").map_err(|e| ParseError::new(offset, "asm", &e.reason))?);
+            }
+            else if token.len() >= 2 && token.starts_with('\'') && token.ends_with('\'') {
+                self = self.push_bytes(&token.as_bytes()[1..token.len()-1]);
+            }
+            else if let Some(opcode) = opcode_from_asm_name(token) {
+                self = self.push_opcode(opcode);
+            }
+            else if token.len()%2 == 0 && token.chars().all(|c| c.is_ascii_hexdigit()) {
+                self = self.push_bytes(&hex::decode(token).unwrap());
+            }
+            else { return Err(ParseError::new(offset, "asm", &format!("unknown token {}", token))) }
+        }
+        return Ok(self)
+    }
+
+    pub fn into_script(self) -> Script {
+        return Script::new(self.bytes)
+    }
+}
+
+// Looks up an opcode as written in asm, with or without the OP_ prefix and including Core's aliases
+fn opcode_from_asm_name(token: &str) -> Option<Opcode> {
+    let name = if token.starts_with("OP_") { token.to_string() } else { "OP_".to_string() + token };
+    return match name.as_str() {
+        "OP_FALSE" => Some(Opcode::OP_0),
+        "OP_TRUE" => Some(Opcode::OP_1),
+        "OP_NOP2" => Some(Opcode::OP_CHECKLOCKTIMEVERIFY),
+        "OP_NOP3" => Some(Opcode::OP_CHECKSEQUENCEVERIFY),
+        _ => Opcode::from_name(&name)
+    }
+}
+
+// Encodes a number as little endian with a sign bit, using as few bytes as possible (0 is empty)
+pub fn encode_script_num(number: i64) -> Vec<u8> {
+    let mut bytes = vec![];
+    let mut abs = number.unsigned_abs();
+    while abs > 0 {
+        bytes.push((abs & 0xff) as u8);
+        abs >>= 8;
+    }
+    if let Some(last) = bytes.last_mut() {
+        if *last & 0x80 != 0 { bytes.push(if number < 0 { 0x80 } else { 0x00 }) } // sign bit needs an extra byte
+        else if number < 0 { *last |= 0x80 }
+    }
+    return bytes
+}
+
+// Interprets bytes as a little endian number with a sign bit, like CScriptNum (without the minimal encoding check)
+pub fn decode_script_num(bytes: &[u8]) -> i64 {
+    if bytes.is_empty() { return 0 }
+    let mut num = 0_i64;
+    for (i, byte) in bytes.iter().enumerate() {
+        num |= (*byte as i64) << (8*i);
+    }
+    let last = bytes[bytes.len()-1];
+    if last & 0x80 != 0 { // sign bit set, so clear it and negate
+        return -(num & !(0x80_i64 << (8*(bytes.len()-1))))
+    }
+    return num
+}
@@ -1,18 +1,64 @@
 use super::transaction;
 use super::parser;
-use sha2::{Sha256,Digest};
+use super::ecc;
+use super::network::Network;
+use super::address;
+use super::psbt;
+use super::core_rpc;
+use super::interpreter;
+use super::audit;
+use super::chain_file;
+use super::compact_chain;
+use super::chain_spec::{ChainSpec, FeeSchedule, Fee};
+use super::covenant::{self, CovenantType};
 
-pub fn run() {
-    println!("Tests running");
+// The secret key behind the signet covenant pubkey, only these tests have it (generatechain takes it as a parameter)
+const SIGNET_KEY_STRING: &str = "eb445ec7e0fd814db1e84622cddad9cd30154ee22bc6c2a4a61f6287be39f2d2";
 
+// A mainnet p2pkh spend, the BIP143 native p2wpkh example and the unsigned cpfp tx from the README
+const MAINNET_TX: &str = concat!(
+    "0100000001813f79011acb80925dfe69b3def355fe914bd1d96a3f5f71bf8303c6a989c7d1000000006b483045022100ed81ff192e75a3fd2304004dcadb746f",
+    "a5e24c5031ccfcf21320b0277457c98f02207a986d955c6e0cb35d446a89d3f56100f4d7f67801c31967743a9c8e10615bed01210349fc4e631e3624a545de3f",
+    "89f5d8684c7b8138bd94bdd531d2e213bf016b278afeffffff02a135ef01000000001976a914bc3b654dca7e56b04dca18f2566cdaf02e8d9ada88ac99c39800",
+    "000000001976a9141c4bc762dd5423e332166702cb75f40df79fea1288ac19430600"
+);
+const SEGWIT_TX: &str = concat!(
+    "01000000000102fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f00000000494830450221008b9d1dc26ba6a9cb62127b02742f",
+    "a9d754cd3bebf337f7a55d114c8e5cdd30be022040529b194ba3f9281a99f2b1c0a19c0489bc22ede944ccf4ecbab4cc618ef3ed01eeffffffef51e1b804cc89",
+    "d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac",
+    "7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac000247304402203609e17b84f6a7d30c80bfa610b5b4542f32",
+    "a8a0d5447a12fb1366d7f01cc44a0220573a954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee0121025476c2e83188368da1ff3e292e",
+    "7acafcdb3566bb0ad253f62fc70f07aeee635711000000"
+);
+const USER_TX: &str = "0200000001f472268495d3c06f48c90a7a7b122baf944dc5b334aa79fcfbdaf2e1545a2a7a0000000000ffffffff01905f010000000000160014c0b42ebdceede249c4d54bee8cf11b5fbe8a14a800000000";
+
+#[test]
+fn legacy_sighashes() {
     // tests sighash_all creation from transaction
-    let sighash_all_data = parser::get_sighash_all_data(&test_tx_for_sighash_all(),0, &"76a9144299ff317fcd12ef19047df66d72454691797bfc88ac".to_string());
-    let sighash_all = hex::encode(Sha256::digest(Sha256::digest(hex::decode(&sighash_all_data).unwrap())));
+    let sighash_all_data = parser::get_sighash_all_data(&test_tx_for_sighash_all(),0, &hex::decode("76a9144299ff317fcd12ef19047df66d72454691797bfc88ac").unwrap());
+    let sighash_all = hex::encode(parser::hash256(&sighash_all_data));
     assert_eq!(sighash_all,"a6b4103f527dfe43dfbadf530c247bac8a98b7463c7c6ad38eed97021d18ffcb");
+    // tests every sighash type against vectors from Bitcoin Core's sighash.json (result shown in reverse byte order)
+    for (transaction, script, input_index, sighash_type, expected) in legacy_sighash_vectors() {
+        let tx = parser::decode_from_hex(transaction).unwrap();
+        let mut sighash = parser::get_legacy_sighash(&tx, input_index, &hex::decode(script).unwrap(), sighash_type);
+        sighash.reverse();
+        assert_eq!(hex::encode(sighash), expected);
+    }
+    // SIGHASH_SINGLE without a matching output signs the number 1 (Core's "one" bug)
+    let mut single_bug_tx = test_tx_for_sighash_all();
+    single_bug_tx.inputs.push(single_bug_tx.inputs[0].clone());
+    let mut one = [0; 32]; one[0] = 1;
+    assert_eq!(parser::get_legacy_sighash(&single_bug_tx, 1, &[], parser::SIGHASH_SINGLE), one);
+    assert_ne!(parser::get_legacy_sighash(&single_bug_tx, 0, &[], parser::SIGHASH_SINGLE), one);
+    assert_eq!(parser::Script::from_asm("OP_CODESEPARATOR OP_DUP OP_CODESEPARATOR 0x4cab").unwrap().without_codeseparators().to_hex(), "764cab"); // malformed tail is kept
+}
 
+#[test]
+fn transaction_round_trips() {
     // Turns transaction hex into an object with readable fields
-    let transaction = "0100000001813f79011acb80925dfe69b3def355fe914bd1d96a3f5f71bf8303c6a989c7d1000000006b483045022100ed81ff192e75a3fd2304004dcadb746fa5e24c5031ccfcf21320b0277457c98f02207a986d955c6e0cb35d446a89d3f56100f4d7f67801c31967743a9c8e10615bed01210349fc4e631e3624a545de3f89f5d8684c7b8138bd94bdd531d2e213bf016b278afeffffff02a135ef01000000001976a914bc3b654dca7e56b04dca18f2566cdaf02e8d9ada88ac99c39800000000001976a9141c4bc762dd5423e332166702cb75f40df79fea1288ac19430600";
-    let tx_content_a = parser::decode_from_hex(transaction);
+    let transaction = MAINNET_TX;
+    let tx_content_a = parser::decode_from_hex(transaction).unwrap();
 
     /* TODO: skip rpc commands if bitcoind isn't running
 
@@ -26,34 +72,996 @@ pub fn run() {
     let content_to_hex = parser::encode_to_hex(&tx_content_a);
     assert!(transaction==content_to_hex);
 
+    // Round trips a segwit transaction (BIP143 native p2wpkh example) and checks txid vs wtxid
+    let segwit_transaction = SEGWIT_TX;
+    let segwit_content = parser::decode_from_hex(segwit_transaction).unwrap();
+    assert_eq!(segwit_content.inputs.len(), 2);
+    assert!(segwit_content.inputs[0].witness.is_empty() && segwit_content.inputs[1].witness.len() == 2);
+    assert_eq!(parser::encode_to_hex(&segwit_content), segwit_transaction);
+    assert_eq!(segwit_content.txid.to_hex(), "e8151a2af31c368a35053ddd4bdb285a8595c769a3ad83e0fa02314a602d4609");
+    assert_eq!(parser::txid_to_hex(&parser::tx_to_wtxid(&segwit_content)), "c36c38370907df2324d9ce9d149d191192f338b37665a82e78e76a12c909b762");
+    assert_eq!(parser::tx_to_wtxid(&tx_content_a), *tx_content_a.txid.as_bytes()); // no witness, so wtxid == txid
+}
+
+#[test]
+fn weight_vsize_and_fees() {
+    // Weight and vsize (witness bytes are discounted), and fees from the spent output values
+    let transaction = MAINNET_TX;
+    let tx_content_a = parser::decode_from_hex(transaction).unwrap();
+    let segwit_transaction = SEGWIT_TX;
+    let segwit_content = parser::decode_from_hex(segwit_transaction).unwrap();
+    assert_eq!((parser::get_weight(&segwit_content), parser::get_vsize(&segwit_content)), (1042, 261));
+    assert_eq!(parser::get_weight(&tx_content_a), transaction.len() as u64/2*4);
+    assert_eq!(segwit_content.fee(&[sats(625_000_000), sats(600_000_000)]), Some(sats(625_000_000 + 600_000_000 - 112_340_000 - 223_450_000)));
+    assert_eq!(segwit_content.fee(&[sats(1), sats(1)]), None); // outputs exceed inputs
+    assert_eq!(segwit_content.fee(&[sats(625_000_000)]), None); // one value per input
+    assert_eq!(segwit_content.fee(&[sats(transaction::MAX_MONEY), sats(1)]), None); // inputs can't add up past MAX_MONEY
+    assert_eq!(parser::get_fee_rate(&tx_content_a, &[sats(32_454_049 + 10_011_545 + 2260)]), Some(10.0));
+}
+
+#[test]
+fn amounts_and_txids() {
+    // Amounts stay within MAX_MONEY, txids are shown reversed and u32 fields keep all their bits
+    let transaction = MAINNET_TX;
+    let tx_content_a = parser::decode_from_hex(transaction).unwrap();
+    assert_eq!((transaction::Amount::from_sat(transaction::MAX_MONEY + 1), transaction::Amount::from_btc(-0.00000001)), (None, None));
+    assert_eq!(transaction::Amount::from_btc(0.1 + 0.2), Some(sats(30_000_000))); // rounded, not truncated to 29_999_999
+    let too_much = transaction.replace("a135ef0100000000", "0140075af0750700"); // 2_100_000_000_000_001 sats
+    assert_eq!(parser::decode_from_hex(&too_much).unwrap_err().field, "output value");
+    assert_eq!(transaction::Txid::from_hex(&tx_content_a.txid.to_string()).unwrap(), tx_content_a.txid);
+    assert_eq!(transaction::Txid::from_hex("00").unwrap_err().field, "txid");
+    assert_eq!((tx_content_a.inputs[0].sequence, tx_content_a.locktime), (transaction::Sequence(0xfffffffe), transaction::LockTime(0x064319)));
+}
+
+#[test]
+fn malformed_transactions() {
+    // Rejects malformed transactions with the offending byte offset and field
+    let transaction = MAINNET_TX;
+    let truncated = parser::decode_from_hex(&transaction[..transaction.len()-2]).unwrap_err();
+    assert_eq!((truncated.offset, truncated.field.as_str()), (transaction.len()/2-4, "locktime"));
+    let trailing = parser::decode_from_hex(&(transaction.to_string() + "00")).unwrap_err();
+    assert_eq!((trailing.offset, trailing.field.as_str()), (transaction.len()/2, "end of transaction"));
+    assert_eq!(parser::decode_from_hex(&transaction[1..]).unwrap_err().field, "hex string"); // odd length
+    assert_eq!(parser::decode_from_hex("0100000001zz").unwrap_err().offset, 5); // invalid hex
+}
+
+#[test]
+fn compact_sizes() {
+    // Compact sizes use the shortest encoding at every boundary and reject anything else
+    for (value, encoded) in [(0, "00"), (0xfc, "fc"), (0xfd, "fdfd00"), (0xffff, "fdffff"), (0x10000, "fe00000100"), (0xffffffff, "feffffffff"), (0x100000000, "ff0000000001000000")] {
+        assert_eq!((hex::encode(parser::compact_size::encode(value)), parser::compact_size::encoded_len(value)), (encoded.to_string(), encoded.len()/2));
+        if value <= parser::compact_size::MAX_SIZE { assert_eq!(parser::Bytestream::new(&hex::decode(encoded).unwrap()).get_varint("size"), Ok(value)) }
+    }
+    for (encoded, reason) in [
+        ("fdfc00", "non-canonical compact size for 252"),
+        ("fe0000ffff", "compact size 4294901760 is larger than 33554432"),
+        ("feffff0000", "non-canonical compact size for 65535"),
+        ("ff0000010000000000", "non-canonical compact size for 65536"),
+        ("fdff", "needs 2 bytes but only 1 remain")
+    ] {
+        assert_eq!(parser::Bytestream::new(&hex::decode(encoded).unwrap()).get_varint("size").unwrap_err().reason, reason);
+    }
+    let mut big_tx = test_tx_for_sighash_all(); // 253 outputs and a 253 byte script both need the 0xfd marker
+    big_tx.outputs = vec![big_tx.outputs[0].clone(); 253];
+    big_tx.outputs[0].scriptpubkey = parser::Script::new(vec![0x6a; 253]);
+    let big_tx_bytes = parser::encode(&big_tx);
+    assert_eq!((hex::encode(&big_tx_bytes[46..49]), hex::encode(&big_tx_bytes[57..60])), ("fdfd00".to_string(), "fdfd00".to_string()));
+    assert_eq!(parser::encode(&parser::decode(&big_tx_bytes).unwrap()), big_tx_bytes);
+    let non_canonical_tx = [&big_tx_bytes[..46], &[0xfe, 0xfd, 0x00, 0x00, 0x00], &big_tx_bytes[49..]].concat();
+    assert_eq!((parser::decode(&non_canonical_tx).unwrap_err().offset, parser::decode(&non_canonical_tx).unwrap_err().field.as_str()), (46, "output count"));
+}
+
+#[test]
+fn p2sh_addresses_and_networks() {
     // Turns an unlock script into a valid p2sh address: 3CK4fEwbMP7heJarmU4eqA3sMbVJyEnU3V
-    let scriptpubkey = "5121022afc20bf379bc96a2f4e9e63ffceb8652b2b6a097f63fbee6ecec2a49a48010e2103a767c7221e9f15f870f1ad9311f5ab937d79fcaeee15bb2c722bca515581b4c052ae";
-    let p2sh_address = parser::script_to_p2sh_address(scriptpubkey); // 3CK4fEwbMP7heJarmU4eqA3sMbVJyEnU3V (prefix 05, not testnet!)
+    let scriptpubkey = hex::decode("5121022afc20bf379bc96a2f4e9e63ffceb8652b2b6a097f63fbee6ecec2a49a48010e2103a767c7221e9f15f870f1ad9311f5ab937d79fcaeee15bb2c722bca515581b4c052ae").unwrap();
+    assert_eq!(parser::script_to_p2sh_address(&scriptpubkey, &Network::Mainnet), "3CK4fEwbMP7heJarmU4eqA3sMbVJyEnU3V");
+    assert!(parser::script_to_p2sh_address(&scriptpubkey, &Network::Regtest).starts_with('2'));
+    assert_eq!(Network::from_name("signet"), Some(Network::Signet));
+    assert_eq!((Network::Regtest.bech32_hrp(), Network::Regtest.default_port()), ("bcrt", 18444));
+}
+
+#[test]
+fn addresses() {
+    // Addresses to scriptpubkeys and back (BIP173/BIP350 and base58check)
+    for (addr, network, scriptpubkey) in [
+        ("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2", Network::Mainnet, "76a91477bff20c60e522dfaa3350c39b030a5d004e839a88ac"),
+        ("3CK4fEwbMP7heJarmU4eqA3sMbVJyEnU3V", Network::Mainnet, "a914748284390f9e263a4b766a75d0633c50426eb87587"),
+        ("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4", Network::Mainnet, "0014751e76e8199196d454941c45d1b3a323f1433bd6"),
+        ("tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7", Network::Signet, "00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262"),
+        ("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0", Network::Mainnet, "512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"),
+        ("tb1pqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesf3hn0c", Network::Testnet, "5120000000c4a5cad46221b2a187905e5266362b99d5e91c6ce24d165dab93e86433")
+    ] {
+        assert_eq!(hex::encode(address::address_to_scriptpubkey(addr, &network).unwrap()), scriptpubkey);
+        assert_eq!(address::scriptpubkey_to_address(&hex::decode(scriptpubkey).unwrap(), &network).unwrap(), addr);
+    }
+    assert_eq!(hex::encode(address::address_to_scriptpubkey("BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4", &Network::Mainnet).unwrap()), "0014751e76e8199196d454941c45d1b3a323f1433bd6");
+    for (addr, network, field) in [
+        ("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN3", Network::Mainnet, "base58 address"), // bad checksum
+        ("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2", Network::Testnet, "address"), // other network
+        ("tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7", Network::Mainnet, "address"),
+        ("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kemeawh", Network::Mainnet, "segwit address"), // v0 with a bech32m checksum
+        ("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqh2y7hd", Network::Mainnet, "segwit address"), // v1 with a bech32 checksum
+        ("tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sL5k7", Network::Testnet, "bech32 address") // mixed case
+    ] {
+        assert_eq!(address::address_to_scriptpubkey(addr, &network).unwrap_err().field, field);
+    }
+    assert_eq!(address::address_network("2NEcniP26o4oF2jUHgTAcncJnKt653gxrLw").unwrap(), Network::Testnet);
+    assert_eq!(address::address_network("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4").unwrap(), Network::Mainnet);
+    assert!(address::decode_segwit_address("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqh2y7hd").unwrap_err().reason.contains("Bech32"));
+    assert_eq!(address::decode_bech32("tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sL5k7").unwrap_err().reason, "mixed case");
+}
+
+#[test]
+fn psbts() {
+    // BIP174 psbts: round trip of a valid vector, then a raw tx and a tx with a scriptsig that aren't valid psbts
+    let transaction = MAINNET_TX;
+    let segwit_transaction = SEGWIT_TX;
+    let valid_psbt = psbt::decode_from_base64(PSBT_VECTOR).unwrap();
+    assert_eq!(psbt::encode_to_base64(&valid_psbt).unwrap(), PSBT_VECTOR);
+    assert_eq!(valid_psbt.inputs[0].non_witness_utxo.as_ref().unwrap().inputs[1].txid.to_hex(), "b490486aec3ae671012dddb2bb08466bef37720a533a894814ff1da743aaf886");
+    assert_eq!((valid_psbt.tx.locktime, valid_psbt.outputs.len(), valid_psbt.input_value(0)), (transaction::LockTime(1257139), 2, Some(sats(200_000_000))));
+    assert_eq!(psbt::decode(&hex::decode(transaction).unwrap()).unwrap_err().field, "psbt magic");
+    assert_eq!(psbt::decode_from_base64(PSBT_WITH_SCRIPTSIG).unwrap_err().reason, "has scriptsigs or witnesses");
+    assert_eq!(psbt::base64_decode("aGVsbG8=").unwrap(), b"hello");
+    assert_eq!(psbt::base64_encode(b"hello!"), "aGVsbG8h");
+    assert_eq!(psbt::base64_decode("aGVsbG8").unwrap_err().field, "base64 string");
+    // Signed inputs become finalized, so extracting gives back the same tx
+    let segwit_psbt = psbt::Psbt::from_tx(&parser::decode_from_hex(segwit_transaction).unwrap());
+    assert!(segwit_psbt.tx.inputs.iter().all(|i| i.scriptsig.is_empty() && i.witness.is_empty()));
+    assert_eq!(parser::encode_to_hex(&segwit_psbt.extract_tx()), segwit_transaction);
+    assert_eq!(psbt::decode(&psbt::encode(&segwit_psbt).unwrap()).unwrap().inputs[1].final_scriptwitness.as_ref().unwrap().len(), 2);
+}
+
+#[test]
+fn psbt_maps_sorted_by_key() {
+    // Unknown keys come out in key order whatever order they were added in, and a key clashing with a known field is rejected
+    let mut psbt = psbt::Psbt::from_tx(&parser::decode_from_hex(SEGWIT_TX).unwrap());
+    psbt.unknown = vec![(vec![0xfc, 0x02], vec![0x02]), (vec![0xfc, 0x01], vec![0x01])];
+    let encoded = psbt::encode(&psbt).unwrap();
+    psbt.unknown.reverse();
+    assert_eq!(psbt::encode(&psbt).unwrap(), encoded);
+    assert_eq!(psbt::decode(&encoded).unwrap().unknown, vec![(vec![0xfc, 0x01], vec![0x01]), (vec![0xfc, 0x02], vec![0x02])]);
+    psbt.unknown.push((vec![0x00], vec![]));
+    assert_eq!(psbt::encode(&psbt).unwrap_err().reason, "duplicate key");
+}
+
+#[test]
+fn signet_covenant_chain() {
+    // The signet covenant chain is reproducible (signatures are deterministic)
+    let (spec, key, covenant_tx) = signet_covenant_tx();
+    assert_eq!(covenant_tx.txid.to_hex(), "9a8727852d451ed7811727489ba0223688fb3876c4f3faedf0e1d10feb445eea");
+    assert_eq!(parser::script_to_p2sh_address(covenant::build_covenant_script(&key.get_pk_bytes(), 1).as_bytes(), &Network::Signet), "2NEcniP26o4oF2jUHgTAcncJnKt653gxrLw");
+    // Hashes of more than 75 bytes need OP_PUSHDATA1 in the op_return, and 80 bytes is the standard maximum
+    let long_hash = [0xab; 80];
+    let long_bump = covenant::build_feebump_tx(&covenant_tx.txid, 1, covenant_tx.outputs[1].value, &long_hash, CovenantType::P2sh);
+    assert_eq!(long_bump.outputs[0].scriptpubkey.as_bytes()[..3], [0x6a, 0x4c, 80]);
+    assert_eq!((long_bump.outputs[0].scriptpubkey.len(), core_rpc::script_type(&long_bump.outputs[0].scriptpubkey)), (83, "nulldata"));
+    assert_eq!(long_bump.inputs[0].scriptsig.instructions().unwrap(), vec![parser::Instruction::Push(covenant::build_bump_script().into_bytes())]);
+    assert_eq!(key.get_pk_string(), spec.pubkey);
+}
+
+#[test]
+fn chain_file_round_trip() {
+    // The published chain file holds everything the generate mode needs, and no secret key
+    let (spec, _, covenant_tx) = signet_covenant_tx();
+    let chain_file = chain_file::generate_chain_file(SIGNET_KEY_STRING, &spec, 3).unwrap();
+    assert_eq!(chain_file.txs[0].txid, covenant_tx.txid);
+    let json = chain_file.to_json();
+    assert!(!json.contains(SIGNET_KEY_STRING));
+    let read_back = chain_file::ChainFile::from_json(&json).unwrap();
+    assert_eq!(read_back.spec, spec);
+    assert_eq!(read_back.tx_strings(), chain_file.tx_strings());
+    assert_eq!(audit::audit_covenant_tx_sequence(&read_back.spec, &read_back.tx_strings()).unwrap().txs, 3);
+}
+
+#[test]
+fn chain_file_errors() {
+    // Chain files of another version, or with a tx that doesn't match the txid next to it, aren't read
+    let chain_file = chain_file::generate_chain_file(SIGNET_KEY_STRING, &ChainSpec::signet(), 2).unwrap();
+    let mut other_version: serde_json::Value = serde_json::from_str(&chain_file.to_json()).unwrap();
+    other_version["version"] = serde_json::json!(chain_file::CHAIN_FILE_VERSION + 1);
+    assert_eq!(chain_file::ChainFile::from_json(&other_version.to_string()).unwrap_err().field, "version");
+    let mut wrong_txid: serde_json::Value = serde_json::from_str(&chain_file.to_json()).unwrap();
+    wrong_txid["txs"][0]["txid"] = wrong_txid["txs"][1]["txid"].clone();
+    assert_eq!(chain_file::ChainFile::from_json(&wrong_txid.to_string()).unwrap_err().field, "tx 0 txid");
+}
+
+#[test]
+fn chain_file_generation_errors() {
+    // Only the covenant's own key signs its chain, and only as many txs as the genesis output pays for
+    let spec = ChainSpec::signet();
+    assert_eq!(chain_file::generate_chain_file(&"11".repeat(32), &spec, 1).unwrap_err().field, "secret key");
+    assert_eq!(chain_file::generate_chain_file(SIGNET_KEY_STRING, &spec, 51).unwrap_err().field, "reps");
+    assert_eq!(chain_file::generate_covenant_tx_sequence(SIGNET_KEY_STRING, &spec, 51).unwrap_err().field, "reps");
+}
+
+#[test]
+fn chain_index_successors() {
+    // The chain index finds the covenant tx that spends the genesis output or a tx of the chain
+    let (spec, _, covenant_tx) = signet_covenant_tx();
+    let chain_file = chain_file::generate_chain_file(SIGNET_KEY_STRING, &spec, 3).unwrap();
+    let index = chain_file.index();
+    let successor = |txid: &transaction::Txid| index.successor(txid).map(|(tx, satoshis)| (tx.txid, satoshis));
+    assert_eq!(successor(&spec.genesis_txid), Some((covenant_tx.txid, spec.input_satoshis)));
+    assert_eq!(successor(&covenant_tx.txid), Some((chain_file.txs[1].txid, sats(98_000))));
+    assert_eq!(successor(&chain_file.txs[2].txid), None);
+}
+
+#[test]
+fn chain_index_steps() {
+    // Steps count from the tx that spends the genesis output, the genesis tx itself isn't one
+    let spec = ChainSpec::signet();
+    let chain_file = chain_file::generate_chain_file(SIGNET_KEY_STRING, &spec, 3).unwrap();
+    let index = chain_file.index();
+    let second_txid = chain_file.txs[1].txid;
+    assert_eq!(index.step(&second_txid), Some(1));
+    assert_eq!(index.step(&spec.genesis_txid), None);
+    assert_eq!(index.remaining(&second_txid), Some(1));
+    assert_eq!(index.remaining(&spec.genesis_txid), Some(3));
+    assert_eq!(index.remaining(&transaction::Txid::default()), None);
+    assert_eq!(index.input_value(2), Some(sats(96_000)));
+    assert_eq!(index.input_value(3), None);
+}
+
+#[test]
+fn chain_index_ranges() {
+    // Ranges of steps are cut off at the end of the chain
+    let chain_file = chain_file::generate_chain_file(SIGNET_KEY_STRING, &ChainSpec::signet(), 3).unwrap();
+    let index = chain_file.index();
+    assert_eq!(index.range(1..10).iter().map(|tx| tx.txid).collect::<Vec<_>>(), vec![chain_file.txs[1].txid, chain_file.txs[2].txid]);
+    assert!(index.range(5..8).is_empty());
+}
+
+#[test]
+fn chain_spec_json() {
+    // Chain specs describe a chain without recompiling, from a json file or as the header of a chain file
+    let spec = ChainSpec::signet();
+    assert_eq!(ChainSpec::from_json(&spec.to_json()).unwrap(), spec);
+    let mut misspelled: serde_json::Value = serde_json::from_str(&spec.to_json()).unwrap();
+    let fee = misspelled.as_object_mut().unwrap().remove("fee").unwrap();
+    misspelled["fees"] = fee;
+    assert_eq!(ChainSpec::from_json(&misspelled.to_string()).unwrap_err().field, "json");
+}
+
+#[test]
+fn signet_spec_costs() {
+    // Each step of the signet chain takes 2000 sats (fee and dust), so its 100,000 sats last 50 steps
+    let spec = ChainSpec::signet();
+    let vsize = spec.covenant().unwrap().vsize;
+    assert_eq!(spec.step_cost(0, vsize), Some(sats(2000)));
+    assert_eq!(spec.affordable_steps(vsize), 50);
+    assert_eq!(spec.chain_file_path(), "signet_chain.json");
+}
+
+#[test]
+fn chain_spec_validation() {
+    // Specs are written by hand, so they're checked when they're read
+    let spec = ChainSpec::signet();
+    let invalid = |invalid_spec: ChainSpec| ChainSpec::from_json(&invalid_spec.to_json()).unwrap_err().field;
+    assert_eq!(invalid(ChainSpec { csv_delay: 0, ..spec.clone() }), "csv_delay");
+    assert_eq!(invalid(ChainSpec { name: "../signet".to_string(), ..spec.clone() }), "name");
+    assert_eq!(invalid(ChainSpec { input_satoshis: sats(1999), ..spec.clone() }), "input_satoshis");
+    assert_eq!(invalid(ChainSpec { pubkey: format!("02{}", "ff".repeat(32)), ..spec }), "public key");
+}
+
+#[test]
+fn chain_spec_shapes_the_chain() {
+    // The spec's csv delay, dust limit, fee and max steps end up in the covenant txs
+    let spec = slow_spec();
+    let chain = chain_file::generate_chain_file(SIGNET_KEY_STRING, &spec, 2).unwrap();
+    assert_eq!(chain.txs[1].inputs[0].sequence, transaction::Sequence(144));
+    assert_eq!(chain.txs[1].outputs[0].value, sats(98_000));
+    assert_eq!(chain.txs[1].outputs[1].value, sats(600));
+    assert_eq!(spec.covenant().unwrap().script.to_asm(), format!("{} OP_CHECKSIGVERIFY 144 OP_CHECKSEQUENCEVERIFY", spec.pubkey));
+    assert_eq!(chain_file::generate_chain_file(SIGNET_KEY_STRING, &spec, 11).unwrap_err().field, "reps");
+}
+
+#[test]
+fn chain_spec_audits() {
+    // A chain passes the audit against its own spec, the signet spec expects another sequence
+    let chain = chain_file::generate_chain_file(SIGNET_KEY_STRING, &slow_spec(), 2).unwrap();
+    assert_eq!(audit::audit_covenant_tx_sequence(&slow_spec(), &chain.tx_strings()).unwrap().remaining, sats(98_000));
+    assert_eq!(audit::audit_covenant_tx_sequence(&ChainSpec::signet(), &chain.tx_strings()).unwrap_err().check, "sequence");
+}
+
+#[test]
+fn chain_spec_round_trips() {
+    // The spec travels with its chain, in chain files and in compact chains
+    let chain = chain_file::generate_chain_file(SIGNET_KEY_STRING, &slow_spec(), 2).unwrap();
+    assert_eq!(chain_file::ChainFile::from_json(&chain.to_json()).unwrap().spec, slow_spec());
+    assert_eq!(compact_chain::reconstruct(&compact_chain::encode(&chain)).unwrap().tx_strings(), chain.tx_strings());
+}
+
+#[test]
+fn chains_side_by_side() {
+    // The generate mode picks the chain that has the txid (both chains spend the same genesis output, the first one wins)
+    let (spec, _, covenant_tx) = signet_covenant_tx();
+    let slow_chain = chain_file::generate_chain_file(SIGNET_KEY_STRING, &slow_spec(), 2).unwrap();
+    let chains = [chain_file::generate_chain_file(SIGNET_KEY_STRING, &spec, 3).unwrap(), slow_chain.clone()];
+    let next = |txid: &transaction::Txid| super::find_covenant_tx(&chains, txid).map(|(tx, satoshis, _)| (tx.txid, satoshis));
+    assert_eq!(next(&slow_chain.txs[0].txid), Some((slow_chain.txs[1].txid, sats(99_000))));
+    assert_eq!(next(&spec.genesis_txid), Some((covenant_tx.txid, spec.input_satoshis)));
+}
+
+#[test]
+fn segwit_chain_specs_make_bids() {
+    // Segwit specs go all the way to a bid, through the json files the generate mode reads
+    for covenant_type in [CovenantType::P2wsh, CovenantType::P2tr] {
+        let spec = ChainSpec { name: covenant_type.name().to_string(), covenant_type, ..ChainSpec::signet() };
+        let read_spec = ChainSpec::from_json(&spec.to_json()).unwrap();
+        assert_eq!(read_spec, spec);
+        assert_eq!(read_spec.chain_file_path(), format!("{}_chain.json", covenant_type.name()));
+        let json = chain_file::generate_chain_file(SIGNET_KEY_STRING, &read_spec, 2).unwrap().to_json();
+        let chain = chain_file::ChainFile::from_json(&json).unwrap();
+        let (cov_hex, _, _) = bid(&chain, &spec.genesis_txid);
+        assert_eq!(cov_hex, parser::encode_to_hex(&chain.txs[0]));
+    }
+}
+
+#[test]
+fn covenant_vsize_estimate() {
+    // Rates are paid on the largest vsize a covenant tx can have, DER signatures are 71 to 73 bytes
+    let (spec, _, covenant_tx) = signet_covenant_tx();
+    let vsize = spec.covenant().unwrap().vsize;
+    assert!(vsize >= parser::get_vsize(&covenant_tx));
+    assert!(vsize <= parser::get_vsize(&covenant_tx) + 2);
+}
+
+#[test]
+fn fee_tiers() {
+    // Each step pays the tier it falls in, rates round up to whole sats
+    let fee = stepped_spec().fee;
+    assert_eq!(fee.fee_at(1, 150), Some(sats(1200)));
+    assert_eq!(fee.fee_at(2, 100), Some(sats(550)));
+    assert_eq!(fee.fee_at(9, 101), Some(sats(556)));
+}
+
+#[test]
+fn rate_fees() {
+    // A rate of 1 sat/vB costs the covenant tx's vsize (plus the dust) every step
+    let spec = ChainSpec { fee: FeeSchedule::rate(1000), ..ChainSpec::signet() };
+    let vsize = spec.covenant().unwrap().vsize;
+    assert_eq!(spec.step_cost(7, vsize), Some(sats(800 + vsize)));
+    assert_eq!(spec.affordable_steps(vsize), 100_000 / (800 + vsize));
+}
+
+#[test]
+fn stepped_fee_chain() {
+    // The covenant txs of a stepped chain pay the fee of their step
+    let spec = stepped_spec();
+    let vsize = spec.covenant().unwrap().vsize;
+    let chain = chain_file::generate_chain_file(SIGNET_KEY_STRING, &spec, 3).unwrap();
+    let index = chain.index();
+    let fees: Vec<u64> = (0..3).map(|step| chain.txs[step].fee(&[index.input_value(step).unwrap()]).unwrap().to_sat()).collect();
+    assert_eq!(fees, vec![1200, 1200, (vsize * 5500).div_ceil(1000)]);
+    assert!(parser::get_fee_rate(&chain.txs[2], &[sats(96_000)]).unwrap() >= 5.5);
+}
+
+#[test]
+fn stepped_fee_chain_audits() {
+    // Auditing a stepped chain against a constant fee catches the first step that pays a rate
+    let chain = chain_file::generate_chain_file(SIGNET_KEY_STRING, &stepped_spec(), 3).unwrap();
+    assert_eq!(audit::audit_covenant_tx_sequence(&stepped_spec(), &chain.tx_strings()).unwrap().txs, 3);
+    assert_eq!(audit::audit_covenant_tx_sequence(&ChainSpec::signet(), &chain.tx_strings()).unwrap_err().tx_index, 2);
+}
+
+#[test]
+fn stepped_fee_chain_round_trips() {
+    // Fee schedules survive chain files and compact chains, and the generate mode knows what each step spends
+    let spec = stepped_spec();
+    let vsize = spec.covenant().unwrap().vsize;
+    let chain = chain_file::generate_chain_file(SIGNET_KEY_STRING, &spec, 3).unwrap();
+    assert_eq!(chain_file::ChainFile::from_json(&chain.to_json()).unwrap().spec, spec);
+    assert_eq!(compact_chain::reconstruct(&compact_chain::encode(&chain)).unwrap().tx_strings(), chain.tx_strings());
+    let (last_tx, last_input, _) = super::find_covenant_tx(std::slice::from_ref(&chain), &chain.txs[1].txid).unwrap();
+    assert_eq!(last_input, sats(96_000));
+    assert_eq!(last_tx.outputs[0].value, sats(96_000 - 800 - (vsize * 5500).div_ceil(1000)));
+}
+
+#[test]
+fn fee_json() {
+    // A constant fee is written as a number, a schedule as its tiers
+    let spec = ChainSpec::signet();
+    let json: serde_json::Value = serde_json::from_str(&spec.to_json()).unwrap();
+    assert_eq!(json["fee"], serde_json::json!(1200));
+    let rate_spec = ChainSpec { fee: FeeSchedule::rate(1000), ..spec };
+    let json: serde_json::Value = serde_json::from_str(&rate_spec.to_json()).unwrap();
+    assert_eq!(json["fee"], serde_json::json!([{"from_step": 0, "sat_per_kvb": 1000}]));
+    assert_eq!(ChainSpec::from_json(&rate_spec.to_json()).unwrap(), rate_spec);
+}
+
+#[test]
+fn invalid_fee_tiers() {
+    // Tiers start at step 0, then at increasing steps, and each pays either sats or a rate
+    let reason = |tiers: Vec<(u64, Fee)>| FeeSchedule { tiers }.validate().unwrap_err().reason;
+    assert_eq!(reason(vec![(1, Fee::Constant(sats(1000)))]), "the first tier has to start at step 0");
+    assert_eq!(reason(vec![(0, Fee::Constant(sats(1000))), (0, Fee::Constant(sats(2000)))]), "tiers have to start at increasing steps");
+    let mut both: serde_json::Value = serde_json::from_str(&ChainSpec::signet().to_json()).unwrap();
+    both["fee"] = serde_json::json!([{"from_step": 0, "sats": 1000, "sat_per_kvb": 1000}]);
+    assert_eq!(ChainSpec::from_json(&both.to_string()).unwrap_err().reason, "the tier from step 0 needs either sats or sat_per_kvb");
+}
+
+#[test]
+fn compact_chains() {
+    // A compact chain only stores the signatures, rebuilding it gives back the same txs and checks every signature
+    let (spec, _, covenant_tx) = signet_covenant_tx();
+    let chain_file = chain_file::generate_chain_file(SIGNET_KEY_STRING, &spec, 3).unwrap();
+    for covenant_type in [CovenantType::P2sh, CovenantType::P2wsh, CovenantType::P2tr] {
+        let typed_spec = ChainSpec { covenant_type, ..spec.clone() };
+        let sequence = chain_file::generate_covenant_tx_sequence(SIGNET_KEY_STRING, &typed_spec, 3).unwrap();
+        let typed_chain = chain_file::ChainFile::new(typed_spec, sequence.iter().map(|tx| parser::decode_from_hex(tx).unwrap()).collect());
+        let compact = compact_chain::encode(&typed_chain);
+        let header = compact_chain::encode(&chain_file::ChainFile::new(typed_chain.spec.clone(), vec![]));
+        assert!(compact.len() - header.len() <= 3 * 75); // a year of 52,560 p2sh steps is under 4 MB
+        let rebuilt = compact_chain::reconstruct(&compact).unwrap();
+        assert_eq!((rebuilt.tx_strings(), rebuilt.spec.covenant_type), (sequence, covenant_type));
+        let mut tampered = compact.clone();
+        *tampered.last_mut().unwrap() ^= 1; // last byte of the last signature
+        assert!(compact_chain::reconstruct(&tampered).unwrap_err().to_string().contains("covenant tx 2"));
+        assert_eq!(compact_chain::decode(&compact[..compact.len() - 1]).unwrap_err().field, "step 2 signature");
+    }
+    let mut compact = compact_chain::encode(&chain_file);
+    compact.push(0);
+    assert_eq!(compact_chain::decode(&compact).unwrap_err().field, "compact chain");
+    compact[4] = 2;
+    assert_eq!(compact_chain::decode(&compact).unwrap_err().field, "version");
+    let too_long = super::generate_next_cov_tx_and_cpfp(std::slice::from_ref(&chain_file), &covenant_tx.txid.to_hex(), &hex::encode([0xab; 81]), "", None).unwrap_err();
+    let too_long = too_long.downcast_ref::<parser::ParseError>().unwrap();
+    assert_eq!((too_long.offset, too_long.field.as_str()), (80, "spacechain hash"));
+}
+
+#[test]
+fn cpfp_spends_the_next_covenant_tx() {
+    // Every covenant type gets a cpfp tx that spends the bump output of the next covenant tx
+    for covenant_type in COVENANT_TYPES {
+        let chain = signet_chain_of_type(covenant_type);
+        let (cov_hex, cpfp_hex, _) = bid(&chain, &chain.txs[0].txid);
+        assert_eq!(cov_hex, parser::encode_to_hex(&chain.txs[1]));
+        assert_eq!(parser::decode_from_hex(&cpfp_hex).unwrap().inputs[1].txid, chain.txs[1].txid);
+    }
+}
+
+#[test]
+fn cpfp_bump_input_is_finalized() {
+    // The bump input needs no signature: p2sh reveals the bump script in the scriptsig, the segwit types in the witness
+    for covenant_type in COVENANT_TYPES {
+        let chain = signet_chain_of_type(covenant_type);
+        let (_, cpfp_hex, _) = bid(&chain, &chain.txs[0].txid);
+        let bump_input = &parser::decode_from_hex(&cpfp_hex).unwrap().inputs[1];
+        assert_eq!(bump_input.scriptsig.is_empty(), covenant_type != CovenantType::P2sh);
+        assert_eq!(bump_input.witness.is_empty(), covenant_type == CovenantType::P2sh);
+    }
+}
+
+#[test]
+fn cpfp_psbt() {
+    // The psbt knows the value of the bump input and extracts to the same cpfp tx
+    for covenant_type in COVENANT_TYPES {
+        let chain = signet_chain_of_type(covenant_type);
+        let (_, cpfp_hex, cpfp_psbt) = bid(&chain, &chain.txs[0].txid);
+        let cpfp_psbt = psbt::decode_from_base64(&cpfp_psbt).unwrap();
+        assert_eq!(cpfp_psbt.input_value(1), Some(chain.txs[1].outputs[1].value));
+        assert_eq!(parser::encode_to_hex(&cpfp_psbt.extract_tx()), cpfp_hex);
+    }
+}
+
+#[test]
+fn segwit_v0_sighashes() {
+    // BIP143 sighashes (native p2wpkh example from the BIP and every sighash type on a p2wpkh spend)
+    let segwit_unsigned = parser::decode_from_hex(concat!(
+        "0100000002fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f0000000000eeffffffef51e1b804cc89d182d279655c3aa89e815b",
+        "1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d0000",
+        "00001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac11000000"
+    )).unwrap();
+    let p2pkh_script_code = hex::decode("76a9141d0f172a0ecb48aee1be1f2687d2963ae33f71a188ac").unwrap();
+    let sighash = parser::get_segwit_v0_sighash(&segwit_unsigned, 1, &p2pkh_script_code, 600_000_000, parser::SIGHASH_ALL);
+    assert_eq!(hex::encode(sighash), "c37af31116d1b27caf68aae9e3ac82f1477929014d5b917657d0eb49478cb670");
+    let single_input_tx = parser::decode_from_hex(concat!(
+        "0200000001cf309ee0839b8aaa3fbc84f8bd32e9c6357e99b49bf6a3af90308c68e762f1d70100000000feffffff0288528c61000000001600146e8d9e07c543",
+        "a309dcdeba8b50a14a991a658c5be0aebb0000000000160014698d8419804a5d5994704d47947889ff7620c004db000000"
+    )).unwrap();
+    let script_code = hex::decode("76a91462744660c6b5133ddeaacbc57d2dc2d7b14d0b0688ac").unwrap();
+    for (sighash_type, expected) in [
+        (0x01, "0a1bc2758dbb5b3a56646f8cafbf63f410cc62b77a482f8b87552683300a7711"),
+        (0x02, "3e275ac8b084f79f756dcd535bffb615cc94a685eefa244d9031eaf22e4cec12"),
+        (0x03, "191a08165ffacc3ea55753b225f323c35fd00d9cc0268081a4a501921fc6ec14"),
+        (0x81, "4b6b612530f94470bbbdef18f57f2990d56b239f41b8728b9a49dc8121de4559"),
+        (0x82, "a7e916d3acd4bb97a21e6793828279aeab02162adf8099ea4f309af81f3d5adb"),
+        (0x83, "d9276e2a48648ddb53a4aaa58314fc2b8067c13013e1913ffb67e0988ce82c78")
+    ] {
+        let mut sighash = parser::get_segwit_v0_sighash(&single_input_tx, 0, &script_code, 1648888940, sighash_type);
+        sighash.reverse();
+        assert_eq!(hex::encode(sighash), expected);
+    }
+}
+
+#[test]
+fn p2wsh_chain() {
+    // The p2wsh chain keeps signature and script in the witness, so it's smaller and its txids can't be malleated
+    let (spec, key, covenant_tx) = signet_covenant_tx();
+    let p2wsh_tx = typed_covenant_tx(CovenantType::P2wsh);
+    assert!(p2wsh_tx.inputs[0].scriptsig.is_empty() && p2wsh_tx.inputs[0].witness.len() == 2);
+    assert_eq!(p2wsh_tx.outputs[0].scriptpubkey.as_bytes(), parser::script_to_p2wsh_script(covenant::build_covenant_script(&key.get_pk_bytes(), 1).as_bytes()));
+    assert!(parser::get_vsize(&p2wsh_tx) < parser::get_vsize(&covenant_tx));
+    let mut malleated = parser::decode(&parser::encode(&p2wsh_tx)).unwrap();
+    malleated.inputs[0].witness[0].push(0);
+    assert_eq!(parser::tx_to_txid(&malleated), p2wsh_tx.txid);
+    let p2wsh_bump = covenant::build_feebump_tx(&p2wsh_tx.txid, 1, sats(800), b"hello world", CovenantType::P2wsh);
+    assert_eq!(p2wsh_bump.inputs[0].witness, vec![covenant::build_bump_script().into_bytes()]);
+}
+
+#[test]
+fn blocks() {
+    // Block parsing, including the merkle root check
+    let block = parser::decode_block_from_hex(test_block_hex()).unwrap();
+    assert_eq!(parser::txid_to_hex(&parser::block_header_to_hash(&block.header)), "00000000b0c5a240b2a61d2e75692224efd4cbecdf6eaf4cc2cf477ca7c270e7");
+    assert_eq!(parser::txid_to_hex(&block.header.prev_blockhash), "00000000e47349de5a0193abc5a2fe0be81cb1d1987e45ab85f3289d54cddc4d");
+    assert_eq!((block.header.version, block.header.time, block.header.bits, block.header.nonce), (1, 1231965655, 486604799, 2067413810));
+    assert_eq!(block.transactions.len(), 2);
+    assert_eq!(parser::encode_block_to_hex(&block), test_block_hex());
+    assert_eq!(parser::decode_block_header(&parser::encode_block_header(&block.header)).unwrap(), block.header);
+    let mut bad_merkle_root = block.header.clone();
+    bad_merkle_root.merkle_root[0] ^= 1;
+    let bad_block = [parser::encode_block_header(&bad_merkle_root), parser::encode_block(&block)[80..].to_vec()].concat();
+    assert_eq!(parser::decode_block(&bad_block).unwrap_err().field, "merkle root");
+    let cut_off = &test_block_hex()[..test_block_hex().len()-2];
+    assert_eq!(parser::decode_block_from_hex(cut_off).unwrap_err().field, "locktime");
+    let txids = [[1; 32], [2; 32], [3; 32]]; // odd levels duplicate their last hash
+    let (left, right) = (parser::hash256(&[[1; 32], [2; 32]].concat()), parser::hash256(&[[3; 32], [3; 32]].concat()));
+    assert_eq!(parser::get_merkle_root(&txids), parser::hash256(&[left, right].concat()));
+}
+
+#[test]
+fn core_json_fields() {
+    // Core's decoderawtransaction json, signatures show their sighash type
+    let tx = parser::decode_from_hex(MAINNET_TX).unwrap();
+    let json: serde_json::Value = serde_json::from_str(&core_rpc::tx_to_json(&tx, &Network::Mainnet)).unwrap();
+    assert_eq!(json["txid"], "452c629d67e41baec3ac6f04fe744b4b9617f8f859c63b3002f8684e7a4fee03");
+    assert_eq!(json["size"], 226);
+    assert_eq!(json["locktime"], 410393);
+    assert!(json["vin"][0]["scriptSig"]["asm"].as_str().unwrap().ends_with("15bed[ALL] 0349fc4e631e3624a545de3f89f5d8684c7b8138bd94bdd531d2e213bf016b278a"));
+    assert_eq!(json["vout"][0]["value"], 0.32454049);
+    assert_eq!(json["vout"][0]["scriptPubKey"]["address"], "1JAHBxA51vwp5C2zpSB15VbxSZK3hVJs2H");
+}
+
+#[test]
+fn core_json_round_trips() {
+    // The json reads back into the same tx, coinbases have no txid or scriptSig in their input
+    let segwit_json = core_rpc::tx_to_json(&parser::decode_from_hex(SEGWIT_TX).unwrap(), &Network::Mainnet);
+    assert_eq!(parser::encode_to_hex(&core_rpc::tx_from_json(&segwit_json).unwrap()), SEGWIT_TX);
+    let coinbase = &parser::decode_block_from_hex(test_block_hex()).unwrap().transactions[0];
+    let coinbase_json = core_rpc::tx_to_json(coinbase, &Network::Mainnet);
+    assert!(coinbase_json.contains("\"coinbase\": \"04ffff001d026e04\"") && !coinbase_json.contains("scriptSig"));
+    assert_eq!(core_rpc::tx_from_json(&coinbase_json).unwrap().txid, coinbase.txid);
+}
+
+#[test]
+fn core_json_errors() {
+    // The json has to be complete and match its txid
+    let segwit_json = core_rpc::tx_to_json(&parser::decode_from_hex(SEGWIT_TX).unwrap(), &Network::Mainnet);
+    let mut changed: serde_json::Value = serde_json::from_str(&segwit_json).unwrap();
+    changed["locktime"] = serde_json::json!(18);
+    assert_eq!(core_rpc::tx_from_json(&changed.to_string()).unwrap_err().field, "txid");
+    assert_eq!(core_rpc::tx_from_json("{\"version\": 2}").unwrap_err().field, "json");
+}
+
+#[test]
+fn script_types() {
+    // Core's names for output script types
+    let multisig = concat!(
+        "5121022afc20bf379bc96a2f4e9e63ffceb8652b2b6a097f63fbee6ecec2a49a48010e",
+        "2103a767c7221e9f15f870f1ad9311f5ab937d79fcaeee15bb2c722bca515581b4c052ae"
+    );
+    for (script, script_type) in [
+        (multisig, "multisig"),
+        ("6a0b68656c6c6f20776f726c64", "nulldata"),
+        ("6a76", "nonstandard"),
+        ("0014751e76e8199196d454941c45d1b3a323f1433bd6", "witness_v0_keyhash"),
+        ("5120147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3", "witness_v1_taproot"),
+        ("51024e73", "anchor"),
+        ("0013751e76e8199196d454941c45d1b3a323f1433b", "nonstandard")
+    ] {
+        assert_eq!(core_rpc::script_type(&parser::Script::from_hex(script).unwrap()), script_type);
+    }
+}
+
+#[test]
+fn taproot_sighashes() {
+    // BIP341 sighashes
+    let (taproot_tx, prevouts) = taproot_test_tx();
+    let taproot_tx = parser::decode_from_hex(taproot_tx).unwrap();
+    let prevouts: Vec<transaction::Output> = prevouts.iter().map(|(script, value)| transaction::Output { value: sats(*value), scriptpubkey: parser::Script::from_hex(script).unwrap() }).collect();
+    for (input_index, sighash_type, expected) in taproot_sighash_vectors() {
+        assert_eq!(hex::encode(parser::get_taproot_sighash(&taproot_tx, input_index, &prevouts, None, sighash_type).unwrap()), expected);
+    }
+    assert!(parser::get_taproot_sighash(&taproot_tx, 0, &prevouts, None, 0x04).is_none());
+    assert!(parser::get_taproot_sighash(&taproot_tx, 0, &prevouts[1..], None, parser::SIGHASH_ALL).is_none());
+}
+
+#[test]
+fn taproot_script_tree() {
+    // BIP341 single leaf script tree: leaf hash, tweaked output key and control block parity
+    let internal_key: [u8; 32] = hex::decode("187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27").unwrap().try_into().unwrap();
+    let leaf_hash = parser::get_tapleaf_hash(&hex::decode("20d85a959b0290bf19bb89ed43c916be835475d013da4b362117393e25a48229b8ac").unwrap());
+    assert_eq!(hex::encode(leaf_hash), "5b75adecf53548f3ec6ad7d78383bf84cc57b55a3127c72b9a2481752dd88b21");
+    assert_eq!(hex::encode(parser::get_taptweak(&internal_key, &leaf_hash)), "cbd8679ba636c1110ea247542cfbd964131a6be84f873f7f3b62a777528ed001");
+    let (output_key, odd) = ecc::get_taproot_output_key(&internal_key, &leaf_hash);
+    assert_eq!(hex::encode(parser::script_to_p2tr_script(&output_key)), "5120147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3");
+    assert!(odd); // control block c1...
+}
+
+#[test]
+fn p2tr_chain() {
+    // The p2tr chain spends the covenant leaf with a schnorr signature over the script path sighash
+    let (spec, key, covenant_tx) = signet_covenant_tx();
+    let p2tr_tx = typed_covenant_tx(CovenantType::P2tr);
+    let covenant_leaf = covenant::build_covenant_script(&key.get_xonly_pk_bytes(), 1);
+    let witness = &p2tr_tx.inputs[0].witness;
+    assert!(p2tr_tx.inputs[0].scriptsig.is_empty() && witness.len() == 3 && witness[0].len() == 64);
+    assert_eq!(witness[1], covenant_leaf.as_bytes());
+    assert_eq!(witness[2][1..], covenant::UNSPENDABLE_INTERNAL_KEY);
+    let prevout = transaction::Output { value: spec.input_satoshis, scriptpubkey: p2tr_tx.outputs[0].scriptpubkey.clone() };
+    let sighash = parser::get_taproot_sighash(&p2tr_tx, 0, &[prevout], Some(&parser::get_tapleaf_hash(covenant_leaf.as_bytes())), parser::SIGHASH_DEFAULT).unwrap();
+    assert_eq!(hex::encode(sighash), "24647c81fce3a2cb4df66daaa08320793b401b5a5bcaa723e1f040fb8d8aec9d");
+    let curve = secp256k1::Secp256k1::verification_only();
+    let sig = secp256k1::schnorr::Signature::from_slice(&witness[0]).unwrap();
+    let pk = secp256k1::XOnlyPublicKey::from_slice(&key.get_xonly_pk_bytes()).unwrap();
+    assert!(curve.verify_schnorr(&sig, &secp256k1::Message::from_slice(&sighash).unwrap(), &pk).is_ok());
+    assert!(parser::get_vsize(&p2tr_tx) < parser::get_vsize(&covenant_tx));
+    let p2tr_bump = covenant::build_feebump_tx(&p2tr_tx.txid, 1, sats(800), b"hello world", CovenantType::P2tr);
+    assert_eq!(p2tr_bump.inputs[0].witness[1], covenant::build_taproot_leaf(&covenant::build_bump_script()).1);
+}
+
+#[test]
+fn script_asm() {
+    // Disassembles the covenant scriptsig and bump script into Core-style asm
+    let (_, key, covenant_tx) = signet_covenant_tx();
+    let covenant_script = covenant::build_covenant_script(&key.get_pk_bytes(), 1);
+    let instructions = covenant_tx.inputs[0].scriptsig.instructions().unwrap();
+    assert_eq!(instructions.len(), 2);
+    assert_eq!(instructions[1], parser::Instruction::Push(covenant_script.as_bytes().to_vec()));
+    assert_eq!(covenant_script.to_asm(), key.get_pk_string() + " OP_CHECKSIGVERIFY 1 OP_CHECKSEQUENCEVERIFY");
+    assert_eq!(covenant::build_bump_script().to_asm(), "0 OP_CHECKSEQUENCEVERIFY OP_1ADD");
+    assert_eq!(parser::Script::from_hex("4c03010203004f0181bb").unwrap().to_asm(), "197121 0 -1 -1 OP_UNKNOWN"); // pushdata1, negative numbers
+    assert_eq!(parser::Script::from_hex("4d0500aabbccddee76").unwrap().to_asm(), "aabbccddee OP_DUP"); // pushdata2
+    assert_eq!(parser::Opcode::from_u8(0xb2).unwrap().name(), "OP_CHECKSEQUENCEVERIFY");
+    assert_eq!(parser::Script::from_hex("4d0500aa").unwrap().to_asm(), "[error]"); // push runs past the end
+}
+
+#[test]
+fn script_from_asm() {
+    // Assembles scripts from asm with minimal pushes (inverse of the above)
+    let (_, key, _) = signet_covenant_tx();
+    let covenant_script = covenant::build_covenant_script(&key.get_pk_bytes(), 1);
+    assert_eq!(parser::Script::from_asm(&covenant_script.to_asm()).unwrap(), covenant_script);
+    assert_eq!(parser::Script::from_asm("OP_0 CHECKSEQUENCEVERIFY OP_1ADD").unwrap(), covenant::build_bump_script());
+    assert_eq!(parser::Script::from_asm("-1 16 17 -129 1000000 'hi' 0x76a9").unwrap().to_hex(), "4f6001110281800340420f02686976a9");
+    assert_eq!(parser::Builder::new().push_bytes(&[7]).push_bytes(&[0x81]).push_bytes(&[0; 76]).into_script().as_bytes()[..4], [0x57, 0x4f, 0x4c, 76]);
+    assert_eq!(parser::Builder::new().push_bytes(&[0; 256]).into_script().as_bytes()[..3], [0x4d, 0x00, 0x01]);
+    assert_eq!(parser::Script::from_asm("OP_DUP OP_FOO").unwrap_err().offset, 7);
+}
+
+#[test]
+fn interpreter_covenant_spends() {
+    // The interpreter accepts every covenant and bump spend (with the covenant's input paying to its own script)
+    let (spec, _, covenant_tx) = signet_covenant_tx();
+    let p2wsh_tx = typed_covenant_tx(CovenantType::P2wsh);
+    let p2tr_tx = typed_covenant_tx(CovenantType::P2tr);
+    for (cov_tx, covenant_type) in [(&covenant_tx, CovenantType::P2sh), (&p2wsh_tx, CovenantType::P2wsh), (&p2tr_tx, CovenantType::P2tr)] {
+        let cov_prevout = transaction::Output { value: spec.input_satoshis, scriptpubkey: cov_tx.outputs[0].scriptpubkey.clone() };
+        assert_eq!(interpreter::verify_tx(cov_tx, std::slice::from_ref(&cov_prevout)), Ok(()));
+        let bump = covenant::build_feebump_tx(&cov_tx.txid, 1, cov_tx.outputs[1].value, b"hello world", covenant_type);
+        assert_eq!(interpreter::verify_tx(&bump, &[cov_tx.outputs[1].clone()]), Ok(()));
+        let mut changed_output = cov_tx.clone();
+        changed_output.outputs[0].value = sats(1000);
+        let error = interpreter::verify_tx(&changed_output, &[cov_prevout]).unwrap_err();
+        assert!(error.reason == "OP_CHECKSIGVERIFY failed" || error.reason == "invalid schnorr signature");
+        let mut version_1 = bump.clone();
+        version_1.version = 1;
+        assert_eq!(interpreter::verify_tx(&version_1, &[cov_tx.outputs[1].clone()]).unwrap_err().reason, "relative locktime requirement not satisfied");
+    }
+    let mut bump = covenant::build_feebump_tx(&covenant_tx.txid, 1, covenant_tx.outputs[1].value, b"hello world", CovenantType::P2sh);
+    bump.inputs[0].sequence = transaction::Sequence(transaction::Sequence::LOCKTIME_DISABLE_FLAG);
+    let error = interpreter::verify_tx(&bump, &[covenant_tx.outputs[1].clone()]).unwrap_err();
+    assert_eq!((error.input_index, error.script.as_str(), error.offset), (0, "redeem script", 1));
+    let wrong_prevout = transaction::Output { value: spec.input_satoshis, scriptpubkey: p2wsh_tx.outputs[0].scriptpubkey.clone() };
+    assert_eq!(interpreter::verify_tx(&covenant_tx, &[wrong_prevout]).unwrap_err().reason, "has to be empty when spending a witness program");
+    assert_eq!(interpreter::verify_input(&covenant_tx, 0, &[None]).unwrap_err().reason, "the spent output is unknown");
+}
+
+#[test]
+fn signatures() {
+    // Signatures parse as strict DER with low S, and verify with just the public key
+    let (spec, key, covenant_tx) = signet_covenant_tx();
+    let covenant_script = covenant::build_covenant_script(&key.get_pk_bytes(), 1);
+    let pk_only = ecc::ECC::from_pk_string(&key.get_pk_string()).unwrap();
+    assert_eq!(pk_only.get_xonly_pk_bytes(), key.get_xonly_pk_bytes());
+    assert_eq!((pk_only.sign_ecdsa_hash_der(&[1; 32]).unwrap_err().field, pk_only.sign_schnorr_hash(&[1; 32]).unwrap_err().field), ("secret key".to_string(), "secret key".to_string()));
+    assert_eq!(ecc::ECC::from_pk_string(&("05".to_string() + &"00".repeat(32))).err().unwrap().field, "public key");
+    let sig_bytes = match &covenant_tx.inputs[0].scriptsig.instructions().unwrap()[0] { parser::Instruction::Push(sig) => sig.clone(), _ => panic!() };
+    let sig = ecc::Signature::from_bytes(&sig_bytes).unwrap();
+    assert_eq!((sig.sighash_type, sig.to_bytes()), (0x01, sig_bytes.clone()));
+    let sighash = parser::get_legacy_sighash(&covenant_tx, 0, covenant_script.as_bytes(), parser::SIGHASH_ALL);
+    assert!(pk_only.verify_ecdsa_hash(&sig, &sighash) && !pk_only.verify_ecdsa_hash(&sig, &[1; 32]));
+    assert_eq!(ecc::Signature::from_bytes(&[&sig_bytes[..], &[0]].concat()).unwrap_err().reason, "isn't strict DER followed by a sighash type");
+    let high_s = "30450220449ccae74e2095ee781bdaa156c3b9b84b45a0d45a97868f10ee1b0320b78857022100cb6572092ac94271e97bac17966609c10700be41f57ceebde312c27de46c477801";
+    assert_eq!(ecc::Signature::from_hex(high_s).unwrap_err().reason, "has a high S value");
+    let mut high_s_tx = covenant_tx.clone(); // consensus still allows high S
+    high_s_tx.inputs[0].scriptsig = parser::Builder::new().push_bytes(&hex::decode(high_s).unwrap()).push_bytes(covenant_script.as_bytes()).into_script();
+    assert_eq!(interpreter::verify_tx(&high_s_tx, &[transaction::Output { value: spec.input_satoshis, scriptpubkey: covenant_tx.outputs[0].scriptpubkey.clone() }]), Ok(()));
+}
+
+#[test]
+fn audit() {
+    // The auditor checks a presigned chain with only the public key and reports the first violation
+    let spec = ChainSpec::signet();
+    for covenant_type in [CovenantType::P2sh, CovenantType::P2wsh, CovenantType::P2tr] {
+        let params = ChainSpec { covenant_type, ..spec.clone() };
+        let mut sequence = chain_file::generate_covenant_tx_sequence(SIGNET_KEY_STRING, &params, 3).unwrap();
+        let report = audit::audit_covenant_tx_sequence(&params, &sequence).unwrap();
+        assert_eq!((report.txs, report.remaining, report.last_txid), (3, sats(94_000), Some(parser::decode_from_hex(&sequence[2]).unwrap().txid)));
+        let mut changed = parser::decode_from_hex(&sequence[1]).unwrap();
+        changed.version = 3;
+        sequence[1] = parser::encode_to_hex(&changed);
+        let violation = audit::audit_covenant_tx_sequence(&params, &sequence).unwrap_err();
+        assert_eq!((violation.tx_index, violation.check, violation.found.as_str()), (1, "signature", "a signature that doesn't match the covenant key"));
+    }
+    let params = spec.clone();
+    let sequence = chain_file::generate_covenant_tx_sequence(SIGNET_KEY_STRING, &params, 3).unwrap();
+    let skipped = audit::audit_covenant_tx_sequence(&params, &[sequence[0].clone(), sequence[2].clone()]).unwrap_err();
+    assert_eq!((skipped.tx_index, skipped.check), (1, "spent outpoint"));
+    assert!(skipped.to_string().contains(&format!("expected: {}:0", parser::decode_from_hex(&sequence[0]).unwrap().txid)));
+    let mut wrong_sequence = parser::decode_from_hex(&sequence[0]).unwrap();
+    wrong_sequence.inputs[0].sequence = transaction::Sequence(0);
+    assert_eq!(audit::audit_covenant_tx_sequence(&params, &[parser::encode_to_hex(&wrong_sequence)]).unwrap_err().check, "sequence");
+    let other_pk = ecc::ECC::new(&"11".repeat(32)).get_pk_string();
+    assert_eq!(audit::audit_covenant_tx_sequence(&ChainSpec { pubkey: other_pk, ..params.clone() }, &sequence).unwrap_err().check, "covenant output");
+    let not_a_point = ChainSpec { pubkey: format!("02{}", "ff".repeat(32)), ..params.clone() };
+    assert_eq!((not_a_point.covenant().unwrap_err().field, audit::audit_covenant_tx_sequence(&not_a_point, &sequence).unwrap_err().check), ("public key".to_string(), "public key"));
+    assert_eq!(audit::audit_covenant_tx_sequence(&ChainSpec { fee: FeeSchedule::constant(sats(200)), ..params.clone() }, &sequence).unwrap_err().check, "covenant value");
+    assert_eq!(audit::audit_covenant_tx_sequence(&ChainSpec { dust_limit: sats(1000), fee: FeeSchedule::constant(sats(1000)), ..params }, &sequence).unwrap_err().check, "bump value");
+}
+
+#[test]
+fn interpreter_standard_spends() {
+    // P2pkh, p2pk and p2wpkh spends from mainnet and BIP143
+    let transaction = MAINNET_TX;
+    let tx_content_a = parser::decode_from_hex(transaction).unwrap();
+    let segwit_transaction = SEGWIT_TX;
+    let segwit_content = parser::decode_from_hex(segwit_transaction).unwrap();
+    let pubkey = match tx_content_a.inputs[0].scriptsig.instructions().unwrap().pop() { Some(parser::Instruction::Push(pubkey)) => pubkey, _ => panic!() };
+    let p2pkh = parser::Script::from_asm(&format!("OP_DUP OP_HASH160 {} OP_EQUALVERIFY OP_CHECKSIG", hex::encode(parser::script_to_p2sh_hash160(&pubkey)))).unwrap();
+    assert_eq!(interpreter::verify_tx(&tx_content_a, &[transaction::Output { value: sats(0), scriptpubkey: p2pkh }]), Ok(()));
+    let segwit_prevouts = [
+        ("2103c9f4836b9a4f77fc0d81f7bcb01b7f1b35916864b9476c241ce9fc198bd25432ac", 625_000_000),
+        ("00141d0f172a0ecb48aee1be1f2687d2963ae33f71a1", 600_000_000)
+    ];
+    let mut segwit_prevouts: Vec<transaction::Output> = segwit_prevouts.iter()
+        .map(|(script, value)| transaction::Output { value: sats(*value), scriptpubkey: parser::Script::from_hex(script).unwrap() })
+        .collect();
+    assert_eq!(interpreter::verify_tx(&segwit_content, &segwit_prevouts), Ok(()));
+    segwit_prevouts[1].value = sats(600_000_001); // segwit signatures commit to the spent amount
+    assert_eq!(interpreter::verify_tx(&segwit_content, &segwit_prevouts).unwrap_err().script, "p2wpkh script");
+}
+
+#[test]
+fn interpreter_opcodes() {
+    // Arithmetic, stack and flow control opcodes (scriptsig, scriptpubkey, error)
+    let (_, _, covenant_tx) = signet_covenant_tx();
+    for (scriptsig, scriptpubkey, error) in [
+        ("2 3", "OP_ADD 5 OP_EQUAL", None),
+        ("", "1 OP_1ADD 2 OP_NUMEQUAL", None),
+        ("-1", "OP_ABS OP_NEGATE -1 OP_NUMEQUAL", None),
+        ("5", "2 6 OP_WITHIN", None),
+        ("1 2 3", "OP_ROT 1 OP_EQUALVERIFY 3 OP_EQUALVERIFY 2 OP_EQUAL", None),
+        ("1 2", "OP_2DUP OP_ADD 3 OP_EQUALVERIFY OP_SWAP 1 OP_EQUALVERIFY 2 OP_EQUAL", None),
+        ("'abc'", "OP_SIZE 3 OP_EQUALVERIFY OP_SHA256 ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad OP_EQUAL", None),
+        ("0", "OP_IF OP_RETURN OP_ELSE 1 OP_ENDIF", None),
+        ("0 0 0", "OP_CHECKMULTISIG", None),
+        ("1", "OP_RETURN", Some((0, "OP_RETURN"))),
+        ("", "OP_DROP 1", Some((0, "needs 1 stack items but there are 0"))),
+        ("0", "OP_IF OP_CAT OP_ENDIF 1", Some((1, "OP_CAT is disabled"))),
+        ("1", "OP_IF 1", Some((2, "OP_IF without OP_ENDIF"))),
+        ("2147483648", "OP_1ADD", Some((0, "number is out of range"))),
+        ("1 0", "OP_DIV", Some((0, "OP_DIV is disabled"))),
+        ("1 0 0", "OP_CHECKMULTISIG", Some((0, "OP_CHECKMULTISIG dummy has to be empty (BIP147)")))
+    ] {
+        let mut tx = covenant_tx.clone();
+        tx.inputs[0].scriptsig = parser::Script::from_asm(scriptsig).unwrap();
+        let prevout = transaction::Output { value: sats(1000), scriptpubkey: parser::Script::from_asm(scriptpubkey).unwrap() };
+        let result = interpreter::verify_tx(&tx, &[prevout]).map_err(|e| (e.offset, e.reason));
+        assert_eq!(result, match error { Some((offset, reason)) => Err((offset, reason.to_string())), None => Ok(()) }, "{} {}", scriptsig, scriptpubkey);
+    }
+}
+
+fn sats(satoshis: u64) -> transaction::Amount {
+    return transaction::Amount::from_sat(satoshis).unwrap()
+}
+
+// The signet spec, its key and the first covenant tx of its chain
+fn signet_covenant_tx() -> (ChainSpec, ecc::ECC, transaction::Content) {
+    let spec = ChainSpec::signet();
+    let key = ecc::ECC::new(SIGNET_KEY_STRING);
+    let covenant_tx = first_covenant_tx(&spec, &key);
+    return (spec, key, covenant_tx)
+}
+
+// The covenant tx that spends the genesis output of a spec's chain
+fn first_covenant_tx(spec: &ChainSpec, key: &ecc::ECC) -> transaction::Content {
+    let covenant = spec.covenant().unwrap();
+    let covenant_satoshis = spec.input_satoshis.checked_sub(spec.step_cost(0, covenant.vsize).unwrap()).unwrap();
+    return covenant::build_covenant_tx(&spec.genesis_txid, spec.genesis_vout, spec.input_satoshis, covenant_satoshis, key, &covenant).unwrap()
+}
+
+// The first covenant tx of the signet chain with another covenant type
+fn typed_covenant_tx(covenant_type: CovenantType) -> transaction::Content {
+    let spec = ChainSpec { covenant_type, ..ChainSpec::signet() };
+    return first_covenant_tx(&spec, &ecc::ECC::new(SIGNET_KEY_STRING))
+}
+
+const COVENANT_TYPES: [CovenantType; 3] = [CovenantType::P2sh, CovenantType::P2wsh, CovenantType::P2tr];
+
+// Two covenant txs of the signet chain with another covenant type
+fn signet_chain_of_type(covenant_type: CovenantType) -> chain_file::ChainFile {
+    return chain_file::generate_chain_file(SIGNET_KEY_STRING, &ChainSpec { covenant_type, ..ChainSpec::signet() }, 2).unwrap()
+}
+
+// A chain with a day's csv delay, less dust and a lower fee than the signet chain
+fn slow_spec() -> ChainSpec {
+    return ChainSpec { name: "slow".to_string(), csv_delay: 144, dust_limit: sats(600), fee: FeeSchedule::constant(sats(400)), max_steps: 10, ..ChainSpec::signet() }
+}
+
+// Pays 1200 sats for the first two steps and 5.5 sat/vB after that
+fn stepped_spec() -> ChainSpec {
+    let fee = FeeSchedule { tiers: vec![(0, Fee::Constant(sats(1200))), (2, Fee::Rate(5500))] };
+    return ChainSpec { name: "stepped".to_string(), fee, ..ChainSpec::signet() }
+}
+
+// Bids "hello world" with the README's unsigned tx on the covenant tx that spends txid
+fn bid(chain: &chain_file::ChainFile, txid: &transaction::Txid) -> (String, String, String) {
+    return super::generate_next_cov_tx_and_cpfp(std::slice::from_ref(chain), &txid.to_hex(), "68656c6c6f20776f726c64", USER_TX, None).unwrap()
+}
+
+// (transaction, script code, input index, sighash type, sighash) covering ALL, NONE, SINGLE and ANYONECANPAY
+pub fn legacy_sighash_vectors() -> Vec<(&'static str, &'static str, u64, u32, &'static str)> {
+    return vec![
+        (
+            concat!(
+                "f2b539a401e4e8402869d5e1502dbc3156dbce93583f516a4947b333260d5af1a34810c6a00200000003525363ffffffff01d305e2000000000005acab535200",
+                "a265fe77"
+            ),
+            "", 0, 2859316840, "41617b27321a830c712638dbb156dae23d4ef181c7a06728ccbf3153ec53d7dd"
+        ),
+        (
+            "c33028b301d5093e1e8397270d75a0b009b2a6509a01861061ab022ca122a6ba935b8513320200000000ffffffff013bcf5a0500000000015200000000",
+            "", 0, 3781554092, "6b1459536f51482f5dbf42d7e561896557461e1e3b6bf67871e2b51faae2832c"
+        ),
+        (
+            concat!(
+                "6f62138301436f33a00b84a26a0457ccbfc0f82403288b9cbae39986b34357cb2ff9b889b302000000045253655335a7ff6701bac9960400000000086552ab65",
+                "6352635200000000"
+            ),
+            "6aac51", 0, 1444414211, "502a2435fd02898d2ff3ab08a3c19078414b32ec9b73d64a944834efc9dae10c"
+        ),
+        (
+            "d3b7421e011f4de0f1cea9ba7458bf3486bee722519efab711a963fa8c100970cf7488b7bb0200000003525352dcd61b300148be5d05000000000000000000",
+            "535251536aac536a", 0, 2334839171, "29aa6d2d752d3310eba20442770ad345b7f6a35f96161ede5f07b33e92053e2a"
+        ),
+        (
+            concat!(
+                "2f7353dd02e395b0a4d16da0f7472db618857cd3de5b9e2789232952a9b154d249102245fd030000000151617fd88f103280b85b0a198198e438e7cab1a4c92b",
+                "a58409709997cc7a65a619eb9eec3c0200000003636aabffffffff0397481c0200000000045300636a0dc97803000000000009d389030000000003ac6a531340",
+                "07bb"
+            ),
+            "0000536552526a", 0, 2382221122, "30c4cd4bd6b291f7e9489cc4b4440a083f93a7664ea1f93e77a9597dab8ded9c"
+        ),
+        (
+            concat!(
+                "32fa0b0804e6ea101e137665a041cc2350b794e59bf42d9b09088b01cde806ec1bbea077df0200000008515153650000006506a11c55904258fa418e57b88b12",
+                "724b81153260d3f4c9f080439789a391ab147aabb0fa0000000007000052ac51ab510986f2a15c0d5e05d20dc876dd2dafa435276d53da7b47c393f20900e55f",
+                "163b97ce0b800000000008ab526a520065636a8087df7d4d9c985fb42308fb09dce704650719140aa6050e8955fa5d2ea46b464a333f87000000000963630063",
+                "6a6565006affffffff01994a0d040000000002536500000000"
+            ),
+            "516563530065", 2, 4131899010, "f58637277d2bc42e18358dc55f7e87e7043f5e33f4ce1fc974e715ef0d3d1c2a"
+        )
+    ]
+}
+
+// The unsigned tx of the BIP341 key path spending vectors and the outputs it spends
+pub fn taproot_test_tx() -> (&'static str, Vec<(&'static str, u64)>) {
+    let tx = concat!(
+        "02000000097de20cbff686da83a54981d2b9bab3586f4ca7e48f57f5b55963115f3b334e9c010000000000000000d7b7cab57b1393ace2d064f4d4a2cb8af6de",
+        "f61273e127517d44759b6dafdd990000000000fffffffff8e1f583384333689228c5d28eac13366be082dc57441760d957275419a418420000000000ffffffff",
+        "f0689180aa63b30cb162a73c6d2a38b7eeda2a83ece74310fda0843ad604853b0100000000feffffffaa5202bdf6d8ccd2ee0f0202afbbb7461d9264a25e5bfd",
+        "3c5a52ee1239e0ba6c0000000000feffffff956149bdc66faa968eb2be2d2faa29718acbfe3941215893a2a3446d32acd050000000000000000000e664b9773b",
+        "88c09c32cb70a2a3e4da0ced63b7ba3b22f848531bbb1d5d5f4c94010000000000000000e9aa6b8e6c9de67619e6a3924ae25696bb7b694bb677a632a74ef7ea",
+        "dfd4eabf0000000000ffffffffa778eb6a263dc090464cd125c466b5a99667720b1c110468831d058aa1b82af10100000000ffffffff0200ca9a3b0000000019",
+        "76a91406afd46bcdfd22ef94ac122aa11f241244a37ecc88ac807840cb0000000020ac9a87f5594be208f8532db38cff670c450ed2fea8fcdefcc9a663f78bab",
+        "962b0065cd1d"
+    );
+    let prevouts = vec![
+        ("512053a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343", 420000000),
+        ("5120147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3", 462000000),
+        ("76a914751e76e8199196d454941c45d1b3a323f1433bd688ac", 294000000),
+        ("5120e4d810fd50586274face62b8a807eb9719cef49c04177cc6b76a9a4251d5450e", 504000000),
+        ("512091b64d5324723a985170e4dc5a0f84c041804f2cd12660fa5dec09fc21783605", 630000000),
+        ("00147dd65592d0ab2fe0d0257d571abf032cd9db93dc", 378000000),
+        ("512075169f4001aa68f15bbed28b218df1d0a62cbbcf1188c6665110c293c907b831", 672000000),
+        ("5120712447206d7a5238acc7ff53fbe94a3b64539ad291c7cdbc490b7577e4b17df5", 546000000),
+        ("512077e30a5522dd9f894c3f8b8bd4c4b2cf82ca7da8a3ea6a239655c39c050ab220", 588000000)
+    ];
+    return (tx, prevouts)
+}
+
+// BIP341 key path spending vectors for taproot_test_tx: (input, sighash type, sighash)
+pub fn taproot_sighash_vectors() -> Vec<(u64, u32, &'static str)> {
+    return vec![
+        (0, 0x03, "2514a6272f85cfa0f45eb907fcb0d121b808ed37c6ea160a5a9046ed5526d555"),
+        (1, 0x83, "325a644af47e8a5a2591cda0ab0723978537318f10e6a63d4eed783b96a71a4d"),
+        (3, 0x01, "bf013ea93474aa67815b1b6cc441d23b64fa310911d991e713cd34c7f5d46669"),
+        (4, 0x00, "4f900a0bae3f1446fd48490c2958b5a023228f01661cda3496a11da502a7f7ef"),
+        (6, 0x02, "15f25c298eb5cdc7eb1d638dd2d45c97c4c59dcaec6679cfc16ad84f30876b85"),
+        (7, 0x82, "cd292de50313804dabe4685e83f923d2969577191a3e1d2882220dca88cbeb10"),
+        (8, 0x81, "cccb739eca6c13a8a89e6e5cd317ffe55669bbda23f2fd37b0f18755e008edd2")
+    ]
+}
+
+// BIP174 valid and invalid (unsigned tx has a scriptsig) test vectors
+const PSBT_VECTOR: &str = concat!(
+    "cHNidP8BAHUCAAAAASaBcTce3/KF6Tet7qSze3gADAVmy7OtZGQXE8pCFxv2AAAAAAD+////AtPf9QUAAAAAGXapFNDFmQPFusKGh2DpD9UhpGZap2UgiKwA4fUFAAAA",
+    "ABepFDVF5uM7gyxHBQ8k0+65PJwDlIvHh7MuEwAAAQD9pQEBAAAAAAECiaPHHqtNIOA3G7ukzGmPopXJRjr6Ljl/hTPMti+VZ+UBAAAAFxYAFL4Y0VKpsBIDna89p95P",
+    "UzSe7LmF/////4b4qkOnHf8USIk6UwpyN+9rRgi7st0tAXHmOuxqSJC0AQAAABcWABT+Pp7xp0XpdNkCxDVZQ6vLNL1TU/////8CAMLrCwAAAAAZdqkUhc/xCX/Z4Ai7",
+    "NK9wnGIZeziXikiIrHL++E4sAAAAF6kUM5cluiHv1irHU6m80GfWx6ajnQWHAkcwRAIgJxK+IuAnDzlPVoMR3HyppolwuAJf3TskAinwf4pfOiQCIAGLONfc0xTnNMkn",
+    "a9b7QPZzMlvEuqFEyADS8vAtsnZcASED0uFWdJQbrUqZY3LLh+GFbTZSYG2YVi/jnF6efkE/IQUCSDBFAiEA0SuFLYXc2WHS9fSrZgZU327tzHlMDDPOXMMJ/7X85Y0C",
+    "IGczio4OFyXBl/saiK9Z9R5E5CVbIBZ8hoQDHAXR8lkqASECI7cr7vCWXRC+B3jv7NYfysb3mk6haTkzgHNEZPhPKrMAAAAAAAAA"
+);
+const PSBT_WITH_SCRIPTSIG: &str = concat!(
+    "cHNidP8BAP0KAQIAAAACqwlJoIxa98SbghL0F+LxWrP1wz3PFTghqBOfh3pbe+QAAAAAakcwRAIgR1lmF5fAGwNrJZKJSGhiGDR9iYZLcZ4ff89X0eURZYcCIFMJ6r9W",
+    "qk2Ikf/REf3xM286KdqGbX+EhtdVRs7tr5MZASEDXNxh/HupccC1AaZGoqg7ECy0OIEhfKaC3Ibi1z+ogpL+////qwlJoIxa98SbghL0F+LxWrP1wz3PFTghqBOfh3pb",
+    "e+QBAAAAAP7///8CYDvqCwAAAAAZdqkUdopAu9dAy+gdmI5x3ipNXHE5ax2IrI4kAAAAAAAAGXapFG9GILVT+glechue4O/p+gOcykWXiKwAAAAAAAABASAA4fUFAAAA",
+    "ABepFDVF5uM7gyxHBQ8k0+65PJwDlIvHhwEEFgAUhdE1N/LiZUBaNNuvqePdoB+4IwgAAAA="
+);
 
-    println!("Tests successful");
+// Mainnet block 00000000b0c5a240b2a61d2e75692224efd4cbecdf6eaf4cc2cf477ca7c270e7 (coinbase and one spend)
+pub fn test_block_hex() -> &'static str {
+    return concat!(
+        "010000004ddccd549d28f385ab457e98d1b11ce80bfea2c5ab93015ade4973e400000000bf4473e53794beae34e64fccc471dace6ae544180816f89591894e0f",
+        "417a914cd74d6e49ffff001d323b3a7b0201000000010000000000000000000000000000000000000000000000000000000000000000ffffffff0804ffff001d",
+        "026e04ffffffff0100f2052a0100000043410446ef0102d1ec5240f0d061a4246c1bdef63fc3dbab7733052fbbf0ecd8f41fc26bf049ebb4f9527f374280259e",
+        "7cfa99c48b0e3f39c51347a19a5819651503a5ac00000000010000000321f75f3139a013f50f315b23b0c9a2b6eac31e2bec98e5891c92466488994226000000",
+        "0049483045022100cb2c6b346a978ab8c61b18b5e9397755cbd17d6eb2fe0083ef32e067fa6c785a02206ce44e613f31d9a6b0517e46f3db1576e9812cc98d15",
+        "9bfdaf759a5014081b5c01ffffffff79cda0945903627c3da1f85fc95d0b8ee3e76ae0cfdc9a65d09744b1f8fc85430000000049483045022047957cdd957cfd",
+        "0becd642f6b84d82f49b6cb4c51a91f49246908af7c3cfdf4a022100e96b46621f1bffcf5ea5982f88cef651e9354f5791602369bf5a82a6cd61a62501ffffff",
+        "fffe09f5fe3ffbf5ee97a54eb5e5069e9da6b4856ee86fc52938c2f979b0f38e82000000004847304402204165be9a4cbab8049e1af9723b96199bfd3e85f44c",
+        "6b4c0177e3962686b26073022028f638da23fc003760861ad481ead4099312c60030d4cb57820ce4d33812a5ce01ffffffff01009d966b01000000434104ea1f",
+        "eff861b51fe3f5f8a3b12d0f4712db80e919548a80839fc47c6a21e66d957e9c5d8cd108c7a2d2324bad71f9904ac0ae7336507d785b17a2c115e427a32fac00",
+        "000000"
+    )
 }
 
 pub fn test_tx_for_sighash_all() -> transaction::Content {
     let (mut inputs, mut outputs) = (vec![], vec![]);
     
     let input = transaction::Input {
-        txid:       "4ba5cfbbeb418055e412682dddb01ccec683a80dd9e12792a273f3b20d4a99b7".to_string(),
+        txid:       transaction::Txid::from_hex("4ba5cfbbeb418055e412682dddb01ccec683a80dd9e12792a273f3b20d4a99b7").unwrap(),
         vout:       0,
-        scriptsig:  String::new(),
-        sequence:   u64::MAX
+        scriptsig:  parser::Script::default(),
+        sequence:   transaction::Sequence::MAX,
+        witness:    vec![]
     };
     inputs.push(input);
 
     let output = transaction::Output {
-        value:         15000,
-        scriptpubkey:  "76a914b3e2819b6262e0b1f19fc7229d75677f347c91ac88ac".to_string()
+        value:         transaction::Amount::from_sat(15000).unwrap(),
+        scriptpubkey:  parser::Script::from_hex("76a914b3e2819b6262e0b1f19fc7229d75677f347c91ac88ac").unwrap()
     };
     outputs.push(output);
 
     return transaction::Content {
-        txid:       String::new(),
+        txid:       transaction::Txid::default(),
         version:    1,
-        locktime:   0,
+        locktime:   transaction::LockTime::ZERO,
         inputs,
         outputs
     }
@@ -1,6 +1,10 @@
-#[derive(Debug)]
+use std::fmt;
+use super::parser::{self, ParseError, Script};
+
+// Txids are stored in serialization order (the reverse of how Core displays them)
+#[derive(Debug, Clone)]
 pub struct Content {
-    pub txid: String, pub version: u64, pub locktime: u64, pub inputs: Vec<Input>, pub outputs: Vec<Output>
+    pub txid: Txid, pub version: u32, pub locktime: LockTime, pub inputs: Vec<Input>, pub outputs: Vec<Output>
 }
 
 impl Content {
@@ -19,7 +23,8 @@ impl Content {
             if a.txid   != b.txid || 
             a.vout      != b.vout ||
             a.scriptsig != b.scriptsig ||
-            a.sequence  != b.sequence {
+            a.sequence  != b.sequence ||
+            a.witness   != b.witness {
                 return false
             }
         }
@@ -32,14 +37,124 @@ impl Content {
         }
         return true
     }
+
+    // Gets the fee given the values of the outputs being spent (one per input, in order)
+    // None if the values don't match the inputs or the outputs spend more than the inputs
+    pub fn fee(&self, prevout_values: &[Amount]) -> Option<Amount> {
+        if prevout_values.len() != self.inputs.len() { return None }
+        let input_total = prevout_values.iter().try_fold(Amount::ZERO, |total, value| total.checked_add(*value))?;
+        let output_total = self.outputs.iter().try_fold(Amount::ZERO, |total, o| total.checked_add(o.value))?;
+        return input_total.checked_sub(output_total)
+    }
+
+    // A coinbase has a single input that spends nothing (zero txid and vout 0xffffffff)
+    pub fn is_coinbase(&self) -> bool {
+        return self.inputs.len() == 1 && self.inputs[0].txid == Txid::default() && self.inputs[0].vout == u32::MAX
+    }
+
+    // Checks if any input carries witness data (determines the BIP144 serialization)
+    pub fn has_witness(&self) -> bool {
+        return self.inputs.iter().any(|i| !i.witness.is_empty())
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Output {
-    pub value: u64, pub scriptpubkey: String
+    pub value: Amount, pub scriptpubkey: Script
 }
 
 #[derive(Debug, Clone)]
 pub struct Input {
-    pub txid: String, pub vout: u64, pub scriptsig: String, pub sequence: u64
+    pub txid: Txid, pub vout: u32, pub scriptsig: Script, pub sequence: Sequence, pub witness: Vec<Vec<u8>>
+}
+
+// A txid in serialization order, use from_hex and to_hex for the order Core displays
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Txid([u8; 32]);
+
+impl Txid {
+    pub fn from_bytes(bytes: [u8; 32]) -> Txid {
+        return Txid(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        return &self.0
+    }
+
+    pub fn from_hex(txid_hex_string: &str) -> Result<Txid, ParseError> {
+        return Ok(Txid(parser::txid_from_hex(txid_hex_string)?))
+    }
+
+    pub fn to_hex(self) -> String {
+        return parser::txid_to_hex(&self.0)
+    }
+}
+
+impl fmt::Display for Txid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}", self.to_hex())
+    }
+}
+
+// No valid output (or sum of outputs) can be worth more than the 21 million bitcoin that will ever exist
+pub const MAX_MONEY: u64 = 21_000_000 * 100_000_000;
+
+// A number of sats between 0 and MAX_MONEY
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Amount(u64);
+
+impl Amount {
+    pub const ZERO: Amount = Amount(0);
+
+    // None above MAX_MONEY
+    pub fn from_sat(satoshis: u64) -> Option<Amount> {
+        if satoshis > MAX_MONEY { return None }
+        return Some(Amount(satoshis))
+    }
+
+    // Converts a bitcoin value (as in Core's json) rounding to the nearest sat, None if negative or above MAX_MONEY
+    pub fn from_btc(btc: f64) -> Option<Amount> {
+        let satoshis = (btc * 100_000_000.0).round();
+        if !(0.0..=MAX_MONEY as f64).contains(&satoshis) { return None }
+        return Some(Amount(satoshis as u64))
+    }
+
+    pub fn to_sat(self) -> u64 {
+        return self.0
+    }
+
+    pub fn checked_add(self, other: Amount) -> Option<Amount> {
+        return Amount::from_sat(self.0.checked_add(other.0)?)
+    }
+
+    pub fn checked_sub(self, other: Amount) -> Option<Amount> {
+        return Some(Amount(self.0.checked_sub(other.0)?))
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}", self.0)
+    }
+}
+
+// nSequence of an input (relative locktime and rbf signalling)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Sequence(pub u32);
+
+impl Sequence {
+    pub const MAX: Sequence = Sequence(u32::MAX); // final, no rbf or relative locktime
+    // BIP68 relative locktime fields
+    pub const LOCKTIME_DISABLE_FLAG: u32 = 1 << 31;
+    pub const LOCKTIME_TYPE_FLAG: u32 = 1 << 22; // set for units of 512 seconds, unset for blocks
+    pub const LOCKTIME_MASK: u32 = 0x0000ffff;
+}
+
+// nLockTime of a transaction (block height below 500_000_000, unix time otherwise)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct LockTime(pub u32);
+
+impl LockTime {
+    pub const ZERO: LockTime = LockTime(0);
+    pub const THRESHOLD: u32 = 500_000_000;
 }
\ No newline at end of file